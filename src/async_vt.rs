@@ -0,0 +1,132 @@
+use std::io::{self, Read, Write, IoSlice};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::io::unix::AsyncFd;
+use crate::error::Result;
+use crate::vt::Vt;
+
+/// Interval between two checks of the active terminal in [`AsyncVt::wait_active`].
+/// 
+/// [`AsyncVt::wait_active`]: crate::AsyncVt::wait_active
+const WAIT_ACTIVE_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Asynchronous wrapper around a [`Vt`], implementing [`AsyncRead`] and [`AsyncWrite`].
+/// 
+/// This type is only available with the `tokio` feature, and must be used from within a Tokio runtime.
+/// 
+/// [`Vt`]: crate::Vt
+/// [`AsyncRead`]: tokio::io::AsyncRead
+/// [`AsyncWrite`]: tokio::io::AsyncWrite
+pub struct AsyncVt<'a> {
+    inner: AsyncFd<Vt<'a>>
+}
+
+impl<'a> AsyncVt<'a> {
+
+    /// Wraps the given terminal, putting it in non-blocking mode
+    /// and registering it with the reactor of the current Tokio runtime.
+    pub fn new(vt: Vt<'a>) -> Result<AsyncVt<'a>> {
+        vt.set_nonblocking(true)?;
+        Ok(AsyncVt {
+            inner: AsyncFd::new(vt)?
+        })
+    }
+
+    /// Returns a reference to the wrapped terminal.
+    pub fn get_ref(&self) -> &Vt<'a> {
+        self.inner.get_ref()
+    }
+
+    /// Returns a mutable reference to the wrapped terminal.
+    pub fn get_mut(&mut self) -> &mut Vt<'a> {
+        self.inner.get_mut()
+    }
+
+    /// Unwraps the terminal, putting it back in blocking mode.
+    pub fn into_inner(self) -> Result<Vt<'a>> {
+        let vt = self.inner.into_inner();
+        vt.set_nonblocking(false)?;
+        Ok(vt)
+    }
+
+    /// Switches to this terminal, waiting asynchronously for the switch to complete.
+    pub async fn switch(&self) -> Result<()> {
+        let vt = self.inner.get_ref();
+        vt.console().activate(vt.number())?;
+        self.wait_active().await
+    }
+
+    /// Waits asynchronously until this terminal becomes the active one.
+    pub async fn wait_active(&self) -> Result<()> {
+        while !self.inner.get_ref().is_active()? {
+            tokio::time::sleep(WAIT_ACTIVE_INTERVAL).await;
+        }
+        Ok(())
+    }
+
+}
+
+impl<'a> AsyncRead for AsyncVt<'a> {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        loop {
+            let mut guard = match this.inner.poll_read_ready_mut(cx) {
+                Poll::Ready(res) => res?,
+                Poll::Pending => return Poll::Pending
+            };
+            let unfilled = buf.initialize_unfilled();
+            match guard.try_io(|inner| inner.get_mut().read(unfilled)) {
+                Ok(Ok(n)) => {
+                    buf.advance(n);
+                    return Poll::Ready(Ok(()));
+                },
+                Ok(Err(e)) => return Poll::Ready(Err(e)),
+                Err(_would_block) => continue
+            }
+        }
+    }
+}
+
+impl<'a> AsyncWrite for AsyncVt<'a> {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        loop {
+            let mut guard = match this.inner.poll_write_ready_mut(cx) {
+                Poll::Ready(res) => res?,
+                Poll::Pending => return Poll::Pending
+            };
+            match guard.try_io(|inner| inner.get_mut().write(buf)) {
+                Ok(res) => return Poll::Ready(res),
+                Err(_would_block) => continue
+            }
+        }
+    }
+
+    fn poll_write_vectored(self: Pin<&mut Self>, cx: &mut Context<'_>, bufs: &[IoSlice<'_>]) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        loop {
+            let mut guard = match this.inner.poll_write_ready_mut(cx) {
+                Poll::Ready(res) => res?,
+                Poll::Pending => return Poll::Pending
+            };
+            match guard.try_io(|inner| inner.get_mut().write_vectored(bufs)) {
+                Ok(res) => return Poll::Ready(res),
+                Err(_would_block) => continue
+            }
+        }
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        true
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(self.get_mut().inner.get_mut().flush())
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.poll_flush(cx)
+    }
+}
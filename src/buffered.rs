@@ -0,0 +1,98 @@
+use std::io::{self, Write};
+use std::ops::Deref;
+use crate::error::Result;
+use crate::vt::Vt;
+
+/// Writer collecting everything written to a [`Vt`] in memory, until it is explicitly flushed.
+/// 
+/// Full-screen applications redraw the screen with many small writes, each one of them being a system call
+/// and possibly making a partially drawn frame visible. Writing to a `BufferedVt` instead makes the whole frame
+/// reach the terminal with a single write when [`Write::flush`] is called.
+/// Pending output is also written when the `BufferedVt` is dropped, ignoring any error.
+/// 
+/// The buffer dereferences to the terminal, so that its state can be inspected,
+/// while [`BufferedVt::get_mut`] gives access to the terminal after writing the pending output.
+/// 
+/// [`Vt`]: crate::Vt
+/// [`Write::flush`]: std::io::Write::flush
+/// [`BufferedVt::get_mut`]: crate::BufferedVt::get_mut
+pub struct BufferedVt<'v, 'a> {
+    vt: &'v mut Vt<'a>,
+    buf: Vec<u8>
+}
+
+impl<'v, 'a> BufferedVt<'v, 'a> {
+
+    /// Returns the output waiting to be written to the terminal.
+    pub fn buffer(&self) -> &[u8] {
+        &self.buf
+    }
+
+    /// Discards the output waiting to be written to the terminal.
+    pub fn discard(&mut self) {
+        self.buf.clear();
+    }
+
+    /// Writes the pending output, and returns a mutable reference to the terminal.
+    pub fn get_mut(&mut self) -> Result<&mut Vt<'a>> {
+        self.write_pending()?;
+        Ok(self.vt)
+    }
+
+    fn write_pending(&mut self) -> io::Result<()> {
+        // Remove only what has been written, so that the rest can be retried if a write fails
+        while !self.buf.is_empty() {
+            match self.vt.write(&self.buf) {
+                Ok(0) => return Err(io::Error::new(io::ErrorKind::WriteZero, "Failed to write the buffered output")),
+                Ok(n) => { self.buf.drain(..n); },
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {},
+                Err(e) => return Err(e)
+            }
+        }
+        Ok(())
+    }
+
+}
+
+impl<'v, 'a> Write for BufferedVt<'v, 'a> {
+
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_pending()?;
+        self.vt.flush()
+    }
+
+}
+
+impl<'v, 'a> Deref for BufferedVt<'v, 'a> {
+    type Target = Vt<'a>;
+
+    fn deref(&self) -> &Vt<'a> {
+        self.vt
+    }
+}
+
+impl<'v, 'a> Drop for BufferedVt<'v, 'a> {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+impl<'a> Vt<'a> {
+
+    /// Returns a [`BufferedVt`] writing to this terminal only when flushed,
+    /// to reduce the number of system calls and the flicker of full-screen redraws.
+    /// 
+    /// [`BufferedVt`]: crate::BufferedVt
+    pub fn buffered(&mut self) -> BufferedVt<'_, 'a> {
+        BufferedVt {
+            vt: self,
+            buf: Vec::new()
+        }
+    }
+
+}
@@ -1,8 +1,115 @@
-use std::io::{self, Read};
+use std::io::{self, Read, Write, Seek, SeekFrom};
 use std::fs::{File, OpenOptions};
-use std::os::unix::io::AsRawFd;
+use std::ops::Deref;
+use std::path::Path;
+use std::os::unix::fs::{FileTypeExt, MetadataExt, OpenOptionsExt};
+use std::os::unix::io::{AsRawFd, AsFd, BorrowedFd, FromRawFd, OwnedFd, RawFd};
+use std::sync::Arc;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+use nix::libc::{c_char, major, minor, O_NOCTTY, O_NONBLOCK};
 use crate::ffi;
-use crate::vt::{Vt, VtNumber, AsVtNumber};
+use crate::error::{Result, VtError};
+use crate::vt::{Vt, OwnedVt, VtNumber, AsVtNumber, TermiosConfig, DropPolicy};
+
+/// Target of a virtual terminal switch, as accepted by [`Console::switch`].
+/// 
+/// [`Console::switch`]: crate::Console::switch
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum SwitchTarget {
+    /// Switch to the terminal with the given number.
+    Absolute(VtNumber),
+    /// Switch to the next allocated terminal, wrapping around to the first one.
+    Next,
+    /// Switch to the previous allocated terminal, wrapping around to the last one.
+    Previous,
+    /// Move by the given number of allocated terminals, forward if positive and backward if negative,
+    /// wrapping around at both ends.
+    Relative(i32)
+}
+
+/// Rotation of the framebuffer console.
+/// Use [`Console::rotation`] and [`Console::set_rotation`] to manage it.
+/// 
+/// [`Console::rotation`]: crate::Console::rotation
+/// [`Console::set_rotation`]: crate::Console::set_rotation
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Rotation {
+    None,
+    Clockwise90,
+    UpsideDown,
+    CounterClockwise90
+}
+
+/// Mouse reporting requested by the application running on the active terminal,
+/// as returned by [`Console::mouse_reporting`].
+/// 
+/// [`Console::mouse_reporting`]: crate::Console::mouse_reporting
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum MouseReporting {
+    /// Mouse events are not reported, so they can be used for selection.
+    Off,
+    /// Only button presses are reported (`ESC [ ? 9 h`).
+    X10,
+    /// Both button presses and releases are reported (`ESC [ ? 1000 h`).
+    X11
+}
+
+/// State of the virtual terminals, as returned by [`Console::vt_state`].
+/// 
+/// [`Console::vt_state`]: crate::Console::vt_state
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct VtState {
+    /// The currently active terminal.
+    pub active: VtNumber,
+    /// Mask of the terminals in use: bit `n` is set if terminal `n` is in use.
+    /// The kernel only reports the first 16 terminals.
+    pub state_mask: u16
+}
+
+impl VtState {
+
+    /// Returns whether the given terminal is in use.
+    /// Since only the first 16 terminals are reported, this always returns `false` for the others.
+    pub fn is_open<N: AsVtNumber>(&self, vt_number: N) -> bool {
+        let n = vt_number.as_vt_number().as_native();
+        n < 16 && self.state_mask & (1 << n) != 0
+    }
+
+    /// Returns an iterator over the numbers of the terminals in use, in ascending order.
+    pub fn in_use(&self) -> impl Iterator<Item = VtNumber> + '_ {
+        (1..16)
+            .map(VtNumber::new)
+            .filter(move |n| self.is_open(*n))
+    }
+
+}
+
+/// Options for [`Console::broadcast`].
+/// 
+/// [`Console::broadcast`]: crate::Console::broadcast
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct BroadcastConfig {
+    /// Skip the terminals in graphics mode, where the text would corrupt the display. Defaults to `true`.
+    pub skip_graphics: bool,
+    /// Ring the bell before the message. Defaults to `true`.
+    pub bell: bool,
+    /// Line printed before the message, like the `Broadcast message from ...` header of `wall`.
+    pub header: Option<String>
+}
+
+impl Default for BroadcastConfig {
+    fn default() -> BroadcastConfig {
+        BroadcastConfig {
+            skip_graphics: true,
+            bell: true,
+            header: None
+        }
+    }
+}
+
+const FBCON_ROTATE_PATH: &str = "/sys/class/graphics/fbcon/rotate";
+pub(crate) const ACTIVE_TTY_PATH: &str = "/sys/class/tty/tty0/active";
 
 /// Handle to a console device file, usually located at `/dev/console`.
 /// This structure allows managing virtual terminals.
@@ -10,21 +117,99 @@ pub struct Console {
     file: File
 }
 
+/// The console a [`Vt`] belongs to, either borrowed or shared through an [`Arc`].
+/// 
+/// [`Vt`]: crate::Vt
+/// [`Arc`]: std::sync::Arc
+pub(crate) enum ConsoleRef<'a> {
+    Borrowed(&'a Console),
+    Shared(Arc<Console>)
+}
+
+impl<'a> Deref for ConsoleRef<'a> {
+    type Target = Console;
+
+    fn deref(&self) -> &Console {
+        match self {
+            ConsoleRef::Borrowed(console) => console,
+            ConsoleRef::Shared(console) => console
+        }
+    }
+}
+
 impl Console {
 
     /// Opens a new handle to the console device file.    
-    pub fn open() -> Result<Console, io::Error> {
+    pub fn open() -> Result<Console> {
+        Console::open_path("/dev/console")
+    }
+
+    /// Opens a new handle to the console device at the given path,
+    /// for example `/dev/tty0` or a console bind-mounted in a container.
+    pub fn open_path<P: AsRef<Path>>(path: P) -> Result<Console> {
         OpenOptions::new()
             .read(true)
             .write(true)
-            .open("/dev/console")
+            .open(path)
             .map(|file| Console { file })
+            .map_err(VtError::from)
+    }
+
+    /// Builds a handle from an already opened console device.
+    /// 
+    /// This allows a privileged launcher to open the console, drop its privileges
+    /// and only then hand the descriptor to this library.
+    /// The caller is responsible for making sure that `fd` actually refers to a console.
+    pub fn from_owned_fd(fd: OwnedFd) -> Console {
+        Console {
+            file: File::from(fd)
+        }
+    }
+
+    /// Opens the first device that is actually a virtual terminal console, trying in order
+    /// `/dev/tty`, `/dev/tty0`, `/dev/vc/0` and `/dev/console`, like the `kbd` tools do.
+    /// 
+    /// This is useful when `/dev/console` is not a virtual terminal (for example on a serial console)
+    /// or cannot be opened. Each device is opened read-write if possible, falling back to write-only
+    /// and read-only. Returns [`VtError::NotAConsole`] if none of the devices is a console.
+    /// 
+    /// [`VtError::NotAConsole`]: crate::VtError::NotAConsole
+    pub fn open_auto() -> Result<Console> {
+        const CANDIDATES: [&str; 4] = ["/dev/tty", "/dev/tty0", "/dev/vc/0", "/dev/console"];
+
+        for path in CANDIDATES.iter() {
+            let file = OpenOptions::new().read(true).write(true).open(path)
+                .or_else(|_| OpenOptions::new().write(true).open(path))
+                .or_else(|_| OpenOptions::new().read(true).open(path));
+            if let Ok(file) = file {
+                if let Ok(ffi::KB_84) | Ok(ffi::KB_101) = ffi::kdgkbtype(file.as_raw_fd()) {
+                    return Ok(Console { file });
+                }
+            }
+        }
+
+        Err(VtError::NotAConsole)
     }
 
     /// Returns the currently active virtual terminal.
-    pub fn current_vt_number(&self) -> io::Result<VtNumber>{
-        let vtstate = ffi::vt_getstate(self.file.as_raw_fd())?;
-        Ok(VtNumber::new(vtstate.v_active.into()))
+    /// 
+    /// If the kernel refuses to report it via ioctl, [`Console::active_from_sysfs`] is used as a fallback.
+    /// 
+    /// [`Console::active_from_sysfs`]: crate::Console::active_from_sysfs
+    pub fn current_vt_number(&self) -> Result<VtNumber>{
+        match ffi::vt_getstate(self.file.as_raw_fd()) {
+            Ok(vtstate) => Ok(VtNumber::new(vtstate.v_active.into())),
+            Err(e) => self.active_from_sysfs().map_err(|_| e)
+        }
+    }
+
+    /// Returns the currently active virtual terminal, as reported by `/sys/class/tty/tty0/active`.
+    /// 
+    /// This does not require any ioctl on the console device, so it works also
+    /// in environments where ioctls are restricted but sysfs is readable.
+    pub fn active_from_sysfs(&self) -> Result<VtNumber> {
+        let mut file = File::open(ACTIVE_TTY_PATH)?;
+        read_active_tty(&mut file)
     }
 
     /// Allocates a new virtual terminal.
@@ -32,7 +217,7 @@ impl Console {
     /// 
     /// [`Console::switch_to`]: crate::Console::switch_to
     /// [`Vt::switch`]: crate::Vt::switch
-    pub fn new_vt(&self) -> io::Result<Vt> {
+    pub fn new_vt(&self) -> Result<Vt<'_>> {
         self.new_vt_with_minimum_number(0)
     }
 
@@ -41,17 +226,36 @@ impl Console {
     /// a maximum number of 16 or 64 vts.
     /// 
     /// To switch to the newly created terminal, use [`Vt::switch`] or [`Console::switch_to`].
-    /// 
+    ///
+    /// Returns [`VtError::InvalidVtNumber`] if `min` is not a valid terminal number.
+    ///
     /// [`Console::switch_to`]: crate::Console::switch_to
     /// [`Vt::switch`]: crate::Vt::switch
-    pub fn new_vt_with_minimum_number(&self, min: i32) -> io::Result<Vt> {
-        
+    /// [`VtError::InvalidVtNumber`]: crate::VtError::InvalidVtNumber
+    pub fn new_vt_with_minimum_number(&self, min: i32) -> Result<Vt<'_>> {
+        self.allocate_vt(min, ConsoleRef::Borrowed(self))
+    }
+
+    /// Allocates a new virtual terminal that keeps a shared reference to this console,
+    /// instead of borrowing it.
+    /// 
+    /// The returned [`OwnedVt`] is `'static`, so it can be stored alongside the console
+    /// or moved to another thread.
+    /// 
+    /// [`OwnedVt`]: crate::OwnedVt
+    pub fn new_vt_owned(self: &Arc<Self>) -> Result<OwnedVt> {
+        self.allocate_vt(0, ConsoleRef::Shared(Arc::clone(self)))
+    }
+
+    fn allocate_vt<'a>(&self, min: i32, console: ConsoleRef<'a>) -> Result<Vt<'a>> {
+        VtNumber::try_new(min)?;
+
         // Get the first available vt number
         let mut n = ffi::vt_openqry(self.file.as_raw_fd())? as i32;
         let vt: Vt;
 
         if n >= min {
-            vt = Vt::with_number(self, n.into())?;
+            vt = Vt::with_number(console, VtNumber::new(n))?;
         } else {
             n = min;
 
@@ -70,7 +274,7 @@ impl Console {
             }
 
             if found {
-                vt = Vt::with_number(self, n.into())?;
+                vt = Vt::with_number(console, VtNumber::new(n))?;
             } else {
 
                 // Slow path: we might be unlucky, and all the first 16 vts are already occupied.
@@ -92,7 +296,7 @@ impl Console {
                 }
 
                 n = first_free;
-                vt = Vt::with_number_and_file(self, n.into(), files.pop().unwrap())?;
+                vt = Vt::with_number_and_file(console, VtNumber::new(n), files.pop().unwrap())?;
 
             }
         }
@@ -100,25 +304,266 @@ impl Console {
         Ok(vt)
     }
 
+    /// Allocates a new virtual terminal and applies the given configuration to it, without switching to it.
+    /// 
+    /// To switch to the newly created terminal, use [`Vt::switch`] or [`Console::switch_to`].
+    /// 
+    /// [`Console::switch_to`]: crate::Console::switch_to
+    /// [`Vt::switch`]: crate::Vt::switch
+    pub fn prepare_vt(&self, config: &TermiosConfig) -> Result<Vt<'_>> {
+        let mut vt = self.new_vt()?;
+        vt.apply_config(config)?;
+        Ok(vt)
+    }
+
     /// Releases the kernel resources for the terminal with the given number.
-    pub(crate) fn disallocate_vt<N:AsVtNumber>(&self, vt_number: N) -> io::Result<()> {
+    pub(crate) fn disallocate_vt<N:AsVtNumber>(&self, vt_number: N) -> Result<()> {
         ffi::vt_disallocate(self.file.as_raw_fd(), vt_number.as_vt_number().as_native())
     }
 
+    /// Releases all the terminals that are not open and not active, like `deallocvt` does,
+    /// returning the numbers of the released terminals.
+    pub fn deallocate_unused(&self) -> Result<Vec<VtNumber>> {
+        let before = self.allocated_vts()?;
+        ffi::vt_disallocate(self.file.as_raw_fd(), 0)?;
+        let after = self.allocated_vts()?;
+        Ok(before.into_iter().filter(|n| !after.contains(n)).collect())
+    }
+
     /// Opens the terminal with the given number.
-    pub fn open_vt<N: AsVtNumber>(&self, vt_number: N) -> io::Result<Vt> {
-        Vt::with_number(self, vt_number.as_vt_number())
+    /// 
+    /// The terminal is left allocated when the returned `Vt` is dropped: use [`Vt::set_drop_policy`] to change this.
+    /// 
+    /// [`Vt::set_drop_policy`]: crate::Vt::set_drop_policy
+    pub fn open_vt<N: AsVtNumber>(&self, vt_number: N) -> Result<Vt<'_>> {
+        let mut vt = Vt::with_number(ConsoleRef::Borrowed(self), vt_number.as_vt_number())?;
+        vt.set_drop_policy(DropPolicy::KeepAllocated);
+        Ok(vt)
     }
 
-    /// Switches to the virtual terminal with the given number.
-    pub fn switch_to<N: AsVtNumber>(&self, vt_number: N) -> io::Result<()> {
-        let n = vt_number.as_vt_number().as_native();
-        ffi::vt_activate(self.file.as_raw_fd(), n)?;
-        ffi::vt_waitactive(self.file.as_raw_fd(), n)
+    /// Opens the terminal with the given number, keeping a shared reference to this console.
+    /// See [`Console::new_vt_owned`] for the details.
+    /// 
+    /// [`Console::new_vt_owned`]: crate::Console::new_vt_owned
+    pub fn open_vt_owned<N: AsVtNumber>(self: &Arc<Self>, vt_number: N) -> Result<OwnedVt> {
+        let mut vt = Vt::with_number(ConsoleRef::Shared(Arc::clone(self)), vt_number.as_vt_number())?;
+        vt.set_drop_policy(DropPolicy::KeepAllocated);
+        Ok(vt)
+    }
+
+    /// Builds a `Vt` from an already opened file, without altering its configuration.
+    /// This is useful for advanced scenarios, like devices at custom paths or files opened by someone else.
+    /// 
+    /// The caller is responsible for making sure that `file` actually refers to the terminal `vt_number`.
+    /// The returned `Vt` uses [`DropPolicy::KeepAllocated`], so it won't be disallocated when dropped.
+    /// 
+    /// [`DropPolicy::KeepAllocated`]: crate::DropPolicy::KeepAllocated
+    pub fn vt_from_number_and_file<N: AsVtNumber>(&self, vt_number: N, file: File) -> Result<Vt<'_>> {
+        Vt::from_number_and_file(ConsoleRef::Borrowed(self), vt_number.as_vt_number(), file)
+    }
+
+    /// Switches to the virtual terminal with the given number, waiting for the switch to complete.
+    /// 
+    /// If the current terminal is in process-controlled switching mode, this blocks until the process
+    /// controlling it allows the switch, possibly forever: use [`Console::activate`]
+    /// and [`Console::wait_active`] to give up after a timeout.
+    /// 
+    /// [`Console::activate`]: crate::Console::activate
+    /// [`Console::wait_active`]: crate::Console::wait_active
+    pub fn switch_to<N: AsVtNumber>(&self, vt_number: N) -> Result<()> {
+        let n = vt_number.as_vt_number();
+        self.activate(n)?;
+        self.wait_active(n, None)
+    }
+
+    /// Requests a switch to the virtual terminal with the given number, without waiting for it to happen.
+    pub fn activate<N: AsVtNumber>(&self, vt_number: N) -> Result<()> {
+        ffi::vt_activate(self.file.as_raw_fd(), vt_number.as_vt_number().as_native())
+    }
+
+    /// Blocks until the terminal with the given number becomes active, or until the given timeout expires.
+    /// 
+    /// Without a timeout, this relies on the kernel to wake up the process when the switch happens.
+    /// With a timeout, the active terminal is polled with an increasing interval, up to half a second.
+    /// Returns an error of kind [`TimedOut`] if the terminal is still not active when the timeout expires.
+    /// 
+    /// [`TimedOut`]: std::io::ErrorKind::TimedOut
+    pub fn wait_active<N: AsVtNumber>(&self, vt_number: N, timeout: Option<Duration>) -> Result<()> {
+        let n = vt_number.as_vt_number();
+        let deadline = match timeout {
+            Some(timeout) => Instant::now() + timeout,
+            None => return ffi::vt_waitactive(self.file.as_raw_fd(), n.as_native())
+        };
+
+        let mut interval = Duration::from_millis(10);
+        while self.current_vt_number()? != n {
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(VtError::new(io::ErrorKind::TimedOut, format!("Terminal {} is still not active", n)));
+            }
+            sleep(interval.min(deadline - now));
+            interval = (interval * 2).min(Duration::from_millis(500));
+        }
+        Ok(())
+    }
+
+    /// Blocks until the user switches to another terminal, returning the number of the newly active one.
+    pub fn wait_for_switch(&self) -> Result<VtNumber> {
+        let mut event = ffi::VtEvent {
+            event: ffi::VT_EVENT_SWITCH,
+            ..Default::default()
+        };
+        ffi::vt_waitevent(self.file.as_raw_fd(), &mut event)?;
+        Ok(VtNumber::new(event.newev as i32))
+    }
+
+    /// Blocks until the user switches to the given terminal.
+    /// 
+    /// Note that this waits for a switch to happen, so it blocks even if the terminal is already active.
+    pub fn wait_for_switch_to<N: AsVtNumber>(&self, vt_number: N) -> Result<()> {
+        let target = vt_number.as_vt_number();
+        while self.wait_for_switch()? != target {}
+        Ok(())
+    }
+
+    /// Switches to the given target, resolving relative targets against the currently active terminal.
+    /// 
+    /// Relative targets only consider allocated terminals, so unallocated numbers are skipped,
+    /// and wrap around: moving past the last allocated terminal continues from the first one and vice versa.
+    /// 
+    /// Returns the number of the terminal that has been switched to.
+    pub fn switch(&self, target: SwitchTarget) -> Result<VtNumber> {
+        let offset = match target {
+            SwitchTarget::Absolute(n) => {
+                self.switch_to(n)?;
+                return Ok(n);
+            },
+            SwitchTarget::Next => 1,
+            SwitchTarget::Previous => -1,
+            SwitchTarget::Relative(offset) => offset
+        };
+
+        let current = self.current_vt_number()?;
+        let mut vts = self.allocated_vts()?;
+        if !vts.contains(&current) {
+            vts.push(current);
+            vts.sort_by_key(|n| n.as_native());
+        }
+
+        let len = vts.len() as i64;
+        let index = vts.iter().position(|n| *n == current).unwrap() as i64;
+        let target = vts[(index + offset as i64).rem_euclid(len) as usize];
+
+        self.switch_to(target)?;
+        Ok(target)
+    }
+
+    /// Returns the state of the virtual terminals, as reported by the kernel.
+    pub fn vt_state(&self) -> Result<VtState> {
+        let vtstate = ffi::vt_getstate(self.file.as_raw_fd())?;
+        Ok(VtState {
+            active: VtNumber::new(vtstate.v_active.into()),
+            state_mask: vtstate.v_state
+        })
+    }
+
+    /// Returns an iterator over the numbers of the allocated terminals, in ascending order.
+    pub fn vts(&self) -> Result<impl Iterator<Item = VtNumber>> {
+        self.allocated_vts().map(Vec::into_iter)
+    }
+
+    /// Returns the numbers of the allocated terminals, in ascending order.
+    fn allocated_vts(&self) -> Result<Vec<VtNumber>> {
+
+        // The kernel only reports the state of the first 16 vts, one bit each
+        let mut vts: Vec<VtNumber> = self.vt_state()?.in_use().collect();
+
+        // For the others, rely on the screen devices, which the kernel registers in sysfs
+        // when a vt is allocated and removes when it is disallocated
+        vts.extend((16..=ffi::MAX_NR_CONSOLES)
+            .filter(|n| Path::new(&format!("/sys/class/vc/vcs{}", n)).exists())
+            .map(VtNumber::new));
+
+        Ok(vts)
+    }
+
+    /// Returns the numbers of the terminals currently held open by at least one process, in ascending order.
+    /// 
+    /// Since opening a terminal allocates it, all the returned terminals are also allocated,
+    /// but not all the allocated terminals are necessarily in use.
+    /// 
+    /// The detection works by scanning the file descriptors listed in `/proc/<pid>/fd`
+    /// for the character devices of the virtual terminals: processes whose descriptors cannot be inspected (usually the ones owned by other users
+    /// when not running as root) are silently skipped, and the result is only a snapshot
+    /// which might already be stale when returned.
+    pub fn vts_in_use(&self) -> Result<Vec<VtNumber>> {
+        let mut numbers = Vec::new();
+        for proc_entry in std::fs::read_dir("/proc")? {
+            let fd_dir = proc_entry?.path().join("fd");
+            let fds = match std::fs::read_dir(fd_dir) {
+                Ok(fds) => fds,
+                Err(_) => continue
+            };
+            for fd in fds.filter_map(std::result::Result::ok) {
+                // Look at the device itself rather than its path, which might be anything
+                let metadata = match std::fs::metadata(fd.path()) {
+                    Ok(metadata) if metadata.file_type().is_char_device() => metadata,
+                    _ => continue
+                };
+                let rdev = metadata.rdev();
+                if major(rdev) != ffi::TTY_MAJOR || minor(rdev) == 0 {
+                    continue;
+                }
+                if let Ok(n) = VtNumber::try_new(minor(rdev) as i32) {
+                    if !numbers.contains(&n) {
+                        numbers.push(n);
+                    }
+                }
+            }
+        }
+
+        numbers.sort_unstable_by_key(|n| n.as_native());
+        Ok(numbers)
+    }
+
+    /// Writes a message to all the allocated terminals, like `wall` does, returning the terminals it was written to.
+    /// 
+    /// The message is written without blocking, so terminals whose output is stuck (for example because
+    /// they are scrolled with `Ctrl+S`) are skipped, like the terminals that cannot be opened
+    /// or are excluded by `config`. Writing to the terminals of other users usually requires root privileges.
+    pub fn broadcast(&self, message: &str, config: &BroadcastConfig) -> Result<Vec<VtNumber>> {
+        // Terminals might not translate newlines, so send explicit carriage returns
+        let mut text = String::from("\r\n");
+        if config.bell {
+            text.push('\x07');
+        }
+        if let Some(ref header) = config.header {
+            text.push_str(header);
+            text.push_str("\r\n\r\n");
+        }
+        for line in message.lines() {
+            text.push_str(line);
+            text.push_str("\r\n");
+        }
+
+        let mut written = Vec::new();
+        for n in self.allocated_vts()? {
+            let mut file = match OpenOptions::new().write(true).custom_flags(O_NOCTTY | O_NONBLOCK).open(format!("/dev/tty{}", n)) {
+                Ok(file) => file,
+                Err(_) => continue
+            };
+            if config.skip_graphics && ffi::kdgetmode(file.as_raw_fd()).ok() == Some(ffi::KD_GRAPHICS) {
+                continue;
+            }
+            if file.write_all(text.as_bytes()).is_ok() {
+                written.push(n);
+            }
+        }
+        Ok(written)
     }
 
     /// Enables or disables virtual terminal switching (usually done with `Ctrl + Alt + F<n>`).
-    pub fn lock_switch(&self, lock: bool) -> io::Result<()> {
+    pub fn lock_switch(&self, lock: bool) -> Result<()> {
         if lock {
             ffi::vt_lockswitch(self.file.as_raw_fd(), 1)
         } else {
@@ -126,16 +571,178 @@ impl Console {
         }
     }
 
-    /// Returns the current console blank timer value. A value of `0` means that the timer is disabled.
+    /// Returns whether the console is currently blanked.
+    pub fn is_blanked(&self) -> Result<bool> {
+        let mut arg = ffi::TIOCL_BLANKEDSCREEN;
+        ffi::tioclinux_call(self.file.as_raw_fd(), &mut arg).map(|blanked| blanked != 0)
+    }
+
+    /// Returns the terminal where kernel messages are printed.
+    /// Terminal number `0` means that messages are printed on the active terminal, which is the default.
+    pub fn kmsg_redirect(&self) -> Result<VtNumber> {
+        // The kernel stores the result in the first byte of the argument
+        let mut arg = ffi::TIOCL_GETKMSGREDIRECT;
+        ffi::tioclinux_bytes(self.file.as_raw_fd(), &mut arg)?;
+        VtNumber::try_new(arg as u8 as i32)
+    }
+
+    /// Prints kernel messages on the given terminal, so that they do not clobber the active one.
+    /// Pass terminal number `0` to print them on the active terminal again.
+    /// 
+    /// This requires the `CAP_SYS_ADMIN` capability.
+    pub fn set_kmsg_redirect<N: AsVtNumber>(&self, vt_number: N) -> Result<()> {
+        let mut arg = [ffi::TIOCL_SETKMSGREDIRECT, vt_number.as_vt_number().as_native() as c_char];
+        ffi::tioclinux_bytes(self.file.as_raw_fd(), arg.as_mut_ptr())
+    }
+
+    /// Returns whether the application running on the active terminal has requested xterm-style mouse reporting.
+    /// 
+    /// Mouse daemons use this to decide whether to forward mouse events as escape sequences
+    /// or to handle them as selections.
+    pub fn mouse_reporting(&self) -> Result<MouseReporting> {
+        // The kernel stores the result in the first byte of the argument
+        let mut arg = ffi::TIOCL_GETMOUSEREPORTING;
+        ffi::tioclinux_bytes(self.file.as_raw_fd(), &mut arg)?;
+        match arg {
+            0 => Ok(MouseReporting::Off),
+            1 => Ok(MouseReporting::X10),
+            2 => Ok(MouseReporting::X11),
+            mode => Err(VtError::new(io::ErrorKind::InvalidData, format!("Unknown mouse reporting mode: {}", mode)))
+        }
+    }
+
+    /// Blocks until the console is no longer blanked, or until the given timeout expires.
+    /// 
+    /// The blank state is polled with an increasing interval, up to half a second.
+    /// Returns an error of kind [`TimedOut`] if the console is still blanked when the timeout expires.
+    /// 
+    /// [`TimedOut`]: std::io::ErrorKind::TimedOut
+    pub fn wait_until_unblanked(&self, timeout: Option<Duration>) -> Result<()> {
+        let deadline = timeout.map(|t| Instant::now() + t);
+        let mut interval = Duration::from_millis(10);
+        while self.is_blanked()? {
+            let mut wait = interval;
+            if let Some(deadline) = deadline {
+                let now = Instant::now();
+                if now >= deadline {
+                    return Err(VtError::new(io::ErrorKind::TimedOut, "The console is still blanked"));
+                }
+                wait = wait.min(deadline - now);
+            }
+            sleep(wait);
+            interval = (interval * 2).min(Duration::from_millis(500));
+        }
+        Ok(())
+    }
+
+    /// Enables or disables the reboot of the system when `Ctrl + Alt + Del` is pressed.
+    /// 
+    /// This uses the `reboot(2)` syscall with `RB_ENABLE_CAD` or `RB_DISABLE_CAD`:
+    /// when disabled, the key combination sends a `SIGINT` to init instead of immediately rebooting.
+    /// The setting is system-wide, not specific to a single terminal, and requires the `CAP_SYS_BOOT` capability:
+    /// without it, an error of kind [`PermissionDenied`] is returned.
+    /// 
+    /// [`PermissionDenied`]: std::io::ErrorKind::PermissionDenied
+    pub fn set_cad_reboot(&self, enabled: bool) -> Result<()> {
+        nix::sys::reboot::set_cad_enabled(enabled)
+            .map_err(VtError::from)
+    }
+
+    /// Returns the current console blank timer value. A zero duration means that the timer is disabled.
     /// To change the blank timer, use the [`Vt::set_blank_timer`] method.
     /// 
     /// [`Vt::set_blank_timer`]: crate::Vt::set_blank_timer
-    pub fn blank_timer(&self) -> io::Result<u32> {
-        OpenOptions::new().read(true).open("/sys/module/kernel/parameters/consoleblank")
-            .and_then(|mut f| {
-                let mut s = String::new();
-                f.read_to_string(&mut s).map(|_| s.trim().parse().expect("Expected consoleblank to contain an unsigned integer"))
-            })
+    pub fn blank_timer(&self) -> Result<Duration> {
+        let mut s = String::new();
+        OpenOptions::new().read(true).open("/sys/module/kernel/parameters/consoleblank")?
+            .read_to_string(&mut s)?;
+        s.trim().parse()
+            .map(Duration::from_secs)
+            .map_err(|_| VtError::new(io::ErrorKind::InvalidData, format!("Unexpected consoleblank value: {}", s.trim())))
+    }
+
+    /// Returns the current rotation of the framebuffer console.
+    /// 
+    /// Returns an error of kind [`Unsupported`] if the console is not backed by a framebuffer.
+    /// 
+    /// [`Unsupported`]: std::io::ErrorKind::Unsupported
+    pub fn rotation(&self) -> Result<Rotation> {
+        let mut s = String::new();
+        OpenOptions::new().read(true).open(FBCON_ROTATE_PATH)
+            .and_then(|mut f| f.read_to_string(&mut s))
+            .map_err(map_fbcon_error)?;
+        match s.trim() {
+            "0" => Ok(Rotation::None),
+            "1" => Ok(Rotation::Clockwise90),
+            "2" => Ok(Rotation::UpsideDown),
+            "3" => Ok(Rotation::CounterClockwise90),
+            other => Err(VtError::new(io::ErrorKind::InvalidData, format!("Unexpected fbcon rotation: {}", other)))
+        }
+    }
+
+    /// Sets the rotation of the framebuffer console.
+    /// 
+    /// Returns an error of kind [`Unsupported`] if the console is not backed by a framebuffer.
+    /// 
+    /// [`Unsupported`]: std::io::ErrorKind::Unsupported
+    pub fn set_rotation(&self, rotation: Rotation) -> Result<()> {
+        let value = match rotation {
+            Rotation::None => 0,
+            Rotation::Clockwise90 => 1,
+            Rotation::UpsideDown => 2,
+            Rotation::CounterClockwise90 => 3
+        };
+        OpenOptions::new().write(true).open(FBCON_ROTATE_PATH)
+            .and_then(|mut f| write!(f, "{}", value))
+            .map_err(map_fbcon_error)
+    }
+
+}
+
+impl AsRawFd for Console {
+    fn as_raw_fd(&self) -> RawFd {
+        self.file.as_raw_fd()
+    }
+}
+
+impl AsFd for Console {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.file.as_fd()
     }
+}
 
-}
\ No newline at end of file
+impl From<Console> for OwnedFd {
+    fn from(console: Console) -> OwnedFd {
+        console.file.into()
+    }
+}
+
+impl FromRawFd for Console {
+    /// Builds a handle from an already opened console device. See [`Console::from_owned_fd`].
+    /// 
+    /// [`Console::from_owned_fd`]: crate::Console::from_owned_fd
+    unsafe fn from_raw_fd(fd: RawFd) -> Console {
+        Console {
+            file: File::from_raw_fd(fd)
+        }
+    }
+}
+
+/// Reads the active terminal from the beginning of an open `/sys/class/tty/tty0/active` file.
+pub(crate) fn read_active_tty(file: &mut File) -> Result<VtNumber> {
+    let mut s = String::new();
+    file.seek(SeekFrom::Start(0))?;
+    file.read_to_string(&mut s)?;
+    s.trim()
+        .strip_prefix("tty")
+        .and_then(|n| n.parse::<i32>().ok())
+        .and_then(|n| VtNumber::try_new(n).ok())
+        .ok_or_else(|| VtError::new(io::ErrorKind::InvalidData, format!("Unexpected active tty: {}", s.trim())))
+}
+
+fn map_fbcon_error(e: io::Error) -> VtError {
+    match e.kind() {
+        io::ErrorKind::NotFound => VtError::new(io::ErrorKind::Unsupported, "The console is not backed by a framebuffer"),
+        _ => e.into()
+    }
+}
@@ -0,0 +1,130 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use nix::libc::{c_int, EACCES, EBUSY, ENOTTY, EPERM};
+
+/// Hint given when an operation fails because of missing privileges.
+const PRIVILEGES_HINT: &str = "Managing virtual terminals usually requires root privileges or the CAP_SYS_TTY_CONFIG capability";
+
+/// Errors returned by the virtual terminal APIs.
+/// 
+/// Use [`VtError::kind`] to get the closest [`io::ErrorKind`] for any error.
+/// A `VtError` can also be converted to an [`io::Error`], so that the `?` operator
+/// can be used in functions returning `io::Result`.
+/// 
+/// [`VtError::kind`]: crate::VtError::kind
+/// [`io::ErrorKind`]: std::io::ErrorKind
+/// [`io::Error`]: std::io::Error
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum VtError {
+    /// The device is not a console or a virtual terminal.
+    NotAConsole,
+    /// The process does not have the privileges needed for the operation.
+    PermissionDenied {
+        /// Suggestion on how to obtain the needed privileges.
+        hint: &'static str
+    },
+    /// The terminal is busy, for example because it is still open while trying to disallocate it.
+    VtBusy,
+    /// The given terminal number is not valid.
+    InvalidVtNumber(i32),
+    /// An ioctl on the console or on a terminal failed.
+    Ioctl {
+        /// Name of the failed ioctl.
+        op: &'static str,
+        /// Error number returned by the kernel.
+        errno: c_int
+    },
+    /// A signal name that is not supported by [`VtSignals`].
+    /// 
+    /// [`VtSignals`]: crate::VtSignals
+    UnknownSignal(String),
+    /// Any other I/O error.
+    Io(io::Error)
+}
+
+/// Result type returned by the virtual terminal APIs.
+pub type Result<T> = std::result::Result<T, VtError>;
+
+impl VtError {
+
+    /// Returns the [`io::ErrorKind`] that best describes this error.
+    /// 
+    /// [`io::ErrorKind`]: std::io::ErrorKind
+    pub fn kind(&self) -> io::ErrorKind {
+        match self {
+            VtError::NotAConsole => io::ErrorKind::Unsupported,
+            VtError::PermissionDenied { .. } => io::ErrorKind::PermissionDenied,
+            VtError::VtBusy => io::ErrorKind::ResourceBusy,
+            VtError::InvalidVtNumber(_) | VtError::UnknownSignal(_) => io::ErrorKind::InvalidInput,
+            VtError::Ioctl { errno, .. } => io::Error::from_raw_os_error(*errno).kind(),
+            VtError::Io(e) => e.kind()
+        }
+    }
+
+    /// Builds a generic I/O error of the given kind.
+    pub(crate) fn new<E>(kind: io::ErrorKind, error: E) -> VtError
+        where E: Into<Box<dyn Error + Send + Sync>>
+    {
+        VtError::Io(io::Error::new(kind, error))
+    }
+
+    /// Builds the error for a failed ioctl from the current `errno`.
+    pub(crate) fn last_ioctl_error(op: &'static str) -> VtError {
+        match io::Error::last_os_error().raw_os_error().unwrap_or(0) {
+            ENOTTY => VtError::NotAConsole,
+            EBUSY => VtError::VtBusy,
+            EPERM | EACCES => VtError::PermissionDenied { hint: PRIVILEGES_HINT },
+            errno => VtError::Ioctl { op, errno }
+        }
+    }
+
+}
+
+impl fmt::Display for VtError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VtError::NotAConsole => write!(f, "Not a console or virtual terminal"),
+            VtError::PermissionDenied { hint } => write!(f, "Permission denied. {}", hint),
+            VtError::VtBusy => write!(f, "Virtual terminal busy"),
+            VtError::InvalidVtNumber(n) => write!(f, "Invalid virtual terminal number: {}", n),
+            VtError::Ioctl { op, errno } => write!(f, "{} failed: {}", op, io::Error::from_raw_os_error(*errno)),
+            VtError::UnknownSignal(name) => write!(f, "Unknown signal name: {}", name),
+            VtError::Io(e) => e.fmt(f)
+        }
+    }
+}
+
+impl Error for VtError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            VtError::Io(e) => Some(e),
+            _ => None
+        }
+    }
+}
+
+impl From<io::Error> for VtError {
+    fn from(e: io::Error) -> VtError {
+        VtError::Io(e)
+    }
+}
+
+impl From<nix::Error> for VtError {
+    fn from(e: nix::Error) -> VtError {
+        match e.as_errno().unwrap_or(nix::errno::Errno::UnknownErrno) as c_int {
+            EPERM | EACCES => VtError::PermissionDenied { hint: PRIVILEGES_HINT },
+            errno => VtError::Io(io::Error::from_raw_os_error(errno))
+        }
+    }
+}
+
+impl From<VtError> for io::Error {
+    fn from(e: VtError) -> io::Error {
+        match e {
+            VtError::Io(e) => e,
+            e => io::Error::new(e.kind(), e)
+        }
+    }
+}
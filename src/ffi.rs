@@ -1,17 +1,85 @@
 use std::io;
+use crate::error::{Result, VtError};
 use std::os::unix::io::RawFd;
 use nix::libc::*;
 
 // Some constants missing from `libc`
 pub const VT_OPENQRY: c_int          = 0x5600;
+pub const VT_GETMODE: c_int          = 0x5601;
+pub const VT_SETMODE: c_int          = 0x5602;
 pub const VT_GETSTATE: c_int         = 0x5603;
+pub const VT_RELDISP: c_int          = 0x5605;
 pub const VT_ACTIVATE: c_int         = 0x5606;
 pub const VT_WAITACTIVE: c_int       = 0x5607;
 pub const VT_DISALLOCATE: c_int      = 0x5608;
 pub const VT_LOCKSWITCH: c_int       = 0x560B;
 pub const VT_UNLOCKSWITCH: c_int     = 0x560C;
+pub const VT_WAITEVENT: c_int        = 0x560E;
+pub const KIOCSOUND: c_int           = 0x4B2F;
+pub const KDMKTONE: c_int            = 0x4B30;
+pub const KDGETLED: c_int            = 0x4B31;
+pub const KDGKBTYPE: c_int           = 0x4B33;
+pub const KB_84: c_char              = 0x01;
+pub const KB_101: c_char             = 0x02;
+pub const KDSETLED: c_int            = 0x4B32;
+pub const KDSETMODE: c_int           = 0x4B3A;
+pub const KDGETMODE: c_int           = 0x4B3B;
+pub const KD_TEXT: c_int             = 0x00;
+pub const KD_GRAPHICS: c_int         = 0x01;
+pub const KDGKBMODE: c_int           = 0x4B44;
+pub const KDSKBMODE: c_int           = 0x4B45;
+pub const KDGKBENT: c_int            = 0x4B46;
+pub const KDSKBENT: c_int            = 0x4B47;
+pub const KDGKBSENT: c_int           = 0x4B48;
+pub const KDSKBSENT: c_int           = 0x4B49;
+pub const KDGETKEYCODE: c_int        = 0x4B4C;
+pub const KDSETKEYCODE: c_int        = 0x4B4D;
+pub const KDSIGACCEPT: c_int         = 0x4B4E;
+pub const K_RAW: c_int               = 0x00;
+pub const K_XLATE: c_int             = 0x01;
+pub const K_MEDIUMRAW: c_int         = 0x02;
+pub const K_UNICODE: c_int           = 0x03;
+pub const K_OFF: c_int               = 0x04;
+pub const K_HOLE: c_ushort           = 0x0200;
+pub const K_NOSUCHMAP: c_ushort      = 0x027F;
+pub const K_SAK: c_ushort            = 0x020F;
+pub const NR_KEYS: usize             = 256;
+pub const MAX_NR_KEYMAPS: usize      = 256;
+pub const MAX_NR_FUNC: usize         = 256;
+pub const KDGKBDIACRUC: c_int        = 0x4BFA;
+pub const KDSKBDIACRUC: c_int        = 0x4BFB;
+pub const MAX_DIACR: usize           = 256;
+pub const KDGKBLED: c_int            = 0x4B64;
+pub const KDSKBLED: c_int            = 0x4B65;
+pub const GIO_UNIMAP: c_int          = 0x4B66;
+pub const PIO_UNIMAP: c_int          = 0x4B67;
+pub const PIO_UNIMAPCLR: c_int       = 0x4B68;
+pub const KDFONTOP: c_int            = 0x4B72;
+pub const KD_FONT_OP_SET: c_uint     = 0;
+pub const KD_FONT_OP_GET: c_uint     = 1;
+pub const KEY_MAX: c_uint            = 0x2FF;
+pub const PIT_TICK_RATE: u32         = 1193182;
+pub const MAX_NR_CONSOLES: c_int     = 63;
+pub const VT_EVENT_SWITCH: c_uint     = 0x0001;
+pub const VT_AUTO: c_char            = 0x00;
+pub const VT_PROCESS: c_char         = 0x01;
+pub const VT_ACKACQ: c_int           = 0x02;
+pub const TTY_MAJOR: c_uint         = 4;
 pub const TIOCL_BLANKSCREEN: c_int   = 14;
 pub const TIOCL_UNBLANKSCREEN: c_int = 4;
+pub const TIOCL_BLANKEDSCREEN: c_int = 15;
+pub const TIOCL_SETSEL: c_char       = 2;
+pub const TIOCL_PASTESEL: c_int      = 3;
+pub const TIOCL_GETSHIFTSTATE: c_char = 6;
+pub const TIOCL_GETMOUSEREPORTING: c_char = 7;
+pub const TIOCL_SETKMSGREDIRECT: c_char = 11;
+pub const TIOCL_SCROLL: c_int        = 13;
+pub const TIOCL_GETKMSGREDIRECT: c_char = 17;
+pub const TIOCL_SELCHAR: c_ushort    = 0;
+pub const TIOCL_SELWORD: c_ushort    = 1;
+pub const TIOCL_SELLINE: c_ushort    = 2;
+pub const TIOCL_SELPOINTER: c_ushort = 3;
+pub const TIOCL_SELCLEAR: c_ushort   = 4;
 
 // Structures for the vt ioctls
 #[repr(C)]
@@ -21,21 +89,114 @@ pub struct VtStat {
 	pub v_state: c_ushort
 }
 
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct VtMode {
+	pub mode: c_char,
+	pub waitv: c_char,
+	pub relsig: c_short,
+	pub acqsig: c_short,
+	pub frsig: c_short
+}
+
+#[repr(C)]
+#[derive(Default)]
+pub struct VtEvent {
+	pub event: c_uint,
+	pub oldev: c_uint,
+	pub newev: c_uint,
+	pub pad: [c_uint; 4]
+}
+
+#[repr(C)]
+pub struct KbKeycode {
+	pub scancode: c_uint,
+	pub keycode: c_uint
+}
+
+#[repr(C)]
+pub struct KbEntry {
+	pub kb_table: c_uchar,
+	pub kb_index: c_uchar,
+	pub kb_value: c_ushort
+}
+
+#[repr(C)]
+pub struct KbSEntry {
+	pub kb_func: c_uchar,
+	pub kb_string: [c_uchar; 512]
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Default)]
+pub struct KbDiacrUc {
+	pub diacr: c_uint,
+	pub base: c_uint,
+	pub result: c_uint
+}
+
+#[repr(C)]
+pub struct KbDiacrsUc {
+	pub kb_cnt: c_uint,
+	pub kbdiacruc: [KbDiacrUc; MAX_DIACR]
+}
+
+#[repr(C)]
+pub struct ConsoleFontOp {
+	pub op: c_uint,
+	pub flags: c_uint,
+	pub width: c_uint,
+	pub height: c_uint,
+	pub charcount: c_uint,
+	pub data: *mut c_uchar
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Default)]
+pub struct UniPair {
+	pub unicode: c_ushort,
+	pub fontpos: c_ushort
+}
+
+// The selection follows the subcode byte directly, without padding
+#[repr(C, packed)]
+pub struct TioclSelection {
+	pub subcode: c_char,
+	pub xs: c_ushort,
+	pub ys: c_ushort,
+	pub xe: c_ushort,
+	pub ye: c_ushort,
+	pub sel_mode: c_ushort
+}
+
+#[repr(C)]
+pub struct UnimapDesc {
+	pub entry_ct: c_ushort,
+	pub entries: *mut UniPair
+}
+
+#[repr(C)]
+pub struct UnimapInit {
+	pub advised_hashsize: c_ushort,
+	pub advised_hashstep: c_ushort,
+	pub advised_hashlevel: c_ushort
+}
+
 macro_rules! ioctl_get_wrapper {
     ($fname:ident, $code:ident, $t:ty) => {
         #[inline]
-        pub fn $fname(fd: RawFd) -> io::Result<$t> {
+        pub fn $fname(fd: RawFd) -> Result<$t> {
             unsafe {
-                let mut data: $t = ::std::mem::uninitialized();
+                let mut data = ::std::mem::MaybeUninit::<$t>::uninit();
                 let res = loop {
-                    let res = ioctl(fd, $code as _, &mut data);
-                    if res != EINTR {
+                    let res = ioctl(fd, $code as _, data.as_mut_ptr());
+                    if res != -1 || io::Error::last_os_error().kind() != io::ErrorKind::Interrupted {
                         break res;
                     }
                 };
                 match res {
-                    -1 => Err(io::Error::from_raw_os_error(res)),
-                    _ => Ok(data)
+                    -1 => Err(VtError::last_ioctl_error(stringify!($code))),
+                    _ => Ok(data.assume_init())
                 }
             }
         }
@@ -45,16 +206,16 @@ macro_rules! ioctl_get_wrapper {
 macro_rules! ioctl_set_wrapper {
     ($fname:ident, $code:ident, $t:ty) => {
         #[inline]
-        pub fn $fname(fd: RawFd, arg: $t) -> io::Result<()> {
+        pub fn $fname(fd: RawFd, arg: $t) -> Result<()> {
             unsafe {
                 let res = loop {
                     let res = ioctl(fd, $code as _, arg);
-                    if res != EINTR {
+                    if res != -1 || io::Error::last_os_error().kind() != io::ErrorKind::Interrupted {
                         break res;
                     }
                 };
                 match res {
-                    -1 => Err(io::Error::from_raw_os_error(res)),
+                    -1 => Err(VtError::last_ioctl_error(stringify!($code))),
                     _ => Ok(())
                 }
             }
@@ -62,12 +223,66 @@ macro_rules! ioctl_set_wrapper {
     };
 }
 
+macro_rules! ioctl_call_wrapper {
+    ($fname:ident, $code:ident, $t:ty) => {
+        #[inline]
+        pub fn $fname(fd: RawFd, arg: $t) -> Result<c_int> {
+            unsafe {
+                let res = loop {
+                    let res = ioctl(fd, $code as _, arg);
+                    if res != -1 || io::Error::last_os_error().kind() != io::ErrorKind::Interrupted {
+                        break res;
+                    }
+                };
+                match res {
+                    -1 => Err(VtError::last_ioctl_error(stringify!($code))),
+                    _ => Ok(res)
+                }
+            }
+        }
+    };
+}
+
 // Ioctl function wrappers
 ioctl_get_wrapper!(vt_openqry, VT_OPENQRY, c_int);
 ioctl_get_wrapper!(vt_getstate, VT_GETSTATE, VtStat);
+ioctl_get_wrapper!(vt_getmode, VT_GETMODE, VtMode);
+ioctl_get_wrapper!(kdgetmode, KDGETMODE, c_int);
+ioctl_get_wrapper!(kdgkbmode, KDGKBMODE, c_int);
+ioctl_get_wrapper!(kdgetled, KDGETLED, c_char);
+ioctl_get_wrapper!(kdgkbled, KDGKBLED, c_char);
+ioctl_get_wrapper!(kdgkbtype, KDGKBTYPE, c_char);
+ioctl_get_wrapper!(tiocgwinsz, TIOCGWINSZ, winsize);
 ioctl_set_wrapper!(vt_activate, VT_ACTIVATE, c_int);
 ioctl_set_wrapper!(vt_waitactive, VT_WAITACTIVE, c_int);
 ioctl_set_wrapper!(vt_disallocate, VT_DISALLOCATE, c_int);
 ioctl_set_wrapper!(vt_lockswitch, VT_LOCKSWITCH, c_int);
 ioctl_set_wrapper!(vt_unlockswitch, VT_UNLOCKSWITCH, c_int);
-ioctl_set_wrapper!(tioclinux, TIOCLINUX, *mut c_int);
\ No newline at end of file
+ioctl_set_wrapper!(vt_setmode, VT_SETMODE, *const VtMode);
+ioctl_set_wrapper!(vt_reldisp, VT_RELDISP, c_int);
+ioctl_set_wrapper!(vt_waitevent, VT_WAITEVENT, *mut VtEvent);
+ioctl_set_wrapper!(kdsetmode, KDSETMODE, c_int);
+ioctl_set_wrapper!(kdskbmode, KDSKBMODE, c_int);
+ioctl_set_wrapper!(kdsigaccept, KDSIGACCEPT, c_ulong);
+ioctl_set_wrapper!(kdsetled, KDSETLED, c_ulong);
+ioctl_set_wrapper!(kdskbled, KDSKBLED, c_ulong);
+ioctl_set_wrapper!(kiocsound, KIOCSOUND, c_ulong);
+ioctl_set_wrapper!(kdmktone, KDMKTONE, c_ulong);
+ioctl_set_wrapper!(kdgetkeycode, KDGETKEYCODE, *mut KbKeycode);
+ioctl_set_wrapper!(kdsetkeycode, KDSETKEYCODE, *const KbKeycode);
+ioctl_set_wrapper!(kdgkbent, KDGKBENT, *mut KbEntry);
+ioctl_set_wrapper!(kdskbent, KDSKBENT, *const KbEntry);
+ioctl_set_wrapper!(kdgkbsent, KDGKBSENT, *mut KbSEntry);
+ioctl_set_wrapper!(kdskbsent, KDSKBSENT, *const KbSEntry);
+ioctl_set_wrapper!(kdgkbdiacruc, KDGKBDIACRUC, *mut KbDiacrsUc);
+ioctl_set_wrapper!(kdskbdiacruc, KDSKBDIACRUC, *const KbDiacrsUc);
+ioctl_set_wrapper!(kdfontop, KDFONTOP, *mut ConsoleFontOp);
+ioctl_set_wrapper!(gio_unimap, GIO_UNIMAP, *mut UnimapDesc);
+ioctl_set_wrapper!(pio_unimap, PIO_UNIMAP, *const UnimapDesc);
+ioctl_set_wrapper!(pio_unimapclr, PIO_UNIMAPCLR, *const UnimapInit);
+ioctl_set_wrapper!(tiocswinsz, TIOCSWINSZ, *const winsize);
+ioctl_set_wrapper!(tiocsctty, TIOCSCTTY, c_int);
+ioctl_set_wrapper!(tioclinux, TIOCLINUX, *mut c_int);
+ioctl_call_wrapper!(tioclinux_call, TIOCLINUX, *mut c_int);
+ioctl_set_wrapper!(tioclinux_bytes, TIOCLINUX, *mut c_char);
+ioctl_set_wrapper!(tioclinux_setsel, TIOCLINUX, *const TioclSelection);
\ No newline at end of file
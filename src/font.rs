@@ -0,0 +1,430 @@
+use std::io;
+use std::os::unix::io::AsRawFd;
+use crate::ffi;
+use crate::error::{Result, VtError};
+use crate::vt::Vt;
+
+/// A console font, made of a set of monochrome glyphs of the same size.
+/// 
+/// Each glyph is stored as `height` rows, each row being `(width + 7) / 8` bytes long,
+/// with the most significant bit of each byte representing the leftmost pixel.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct Font {
+    width: u32,
+    height: u32,
+    charcount: u32,
+    data: Vec<u8>
+}
+
+impl Font {
+
+    /// Creates a new font from the raw glyph data.
+    /// 
+    /// Returns an error of kind [`InvalidInput`] if the size of the data does not match
+    /// the given dimensions, or if the dimensions are not supported by the kernel.
+    /// 
+    /// [`InvalidInput`]: std::io::ErrorKind::InvalidInput
+    pub fn new(width: u32, height: u32, charcount: u32, data: Vec<u8>) -> Result<Font> {
+        if width == 0 || width > MAX_WIDTH || height == 0 || height > MAX_HEIGHT || charcount == 0 || charcount > MAX_CHARCOUNT {
+            return Err(VtError::new(io::ErrorKind::InvalidInput, format!("Unsupported font size: {}x{}, {} glyphs", width, height, charcount)));
+        }
+        let font = Font { width, height, charcount, data };
+        if font.data.len() != font.glyph_size() * charcount as usize {
+            return Err(VtError::new(io::ErrorKind::InvalidInput, "Font data does not match the font size"));
+        }
+        Ok(font)
+    }
+
+    /// Parses a font in the PC Screen Font format, either version 1 or 2,
+    /// which is the format of the fonts usually found in `/usr/share/consolefonts`.
+    /// Note that compressed fonts must be decompressed first.
+    /// 
+    /// Any unicode table embedded in the font is ignored.
+    /// Returns an error of kind [`InvalidData`] if the data is not a valid PSF font.
+    /// 
+    /// [`InvalidData`]: std::io::ErrorKind::InvalidData
+    pub fn from_psf_bytes(bytes: &[u8]) -> Result<Font> {
+        let invalid = |msg: &str| VtError::new(io::ErrorKind::InvalidData, msg.to_string());
+
+        let (width, height, charcount, data_start) = if bytes.starts_with(&PSF1_MAGIC) {
+            if bytes.len() < 4 {
+                return Err(invalid("Truncated PSF1 header"));
+            }
+            let charcount = if bytes[2] & PSF1_MODE512 != 0 { 512 } else { 256 };
+            (8, bytes[3] as u32, charcount, 4)
+        } else if bytes.starts_with(&PSF2_MAGIC) {
+            if bytes.len() < 32 {
+                return Err(invalid("Truncated PSF2 header"));
+            }
+            let field = |i: usize| u32::from_le_bytes([bytes[i * 4], bytes[i * 4 + 1], bytes[i * 4 + 2], bytes[i * 4 + 3]]);
+            let (header_size, charcount, charsize, height, width) = (field(2), field(4), field(5), field(6), field(7));
+
+            // Reject sizes the kernel does not support before doing any arithmetic with them
+            if width == 0 || width > MAX_WIDTH || height == 0 || height > MAX_HEIGHT || charcount == 0 || charcount > MAX_CHARCOUNT {
+                return Err(invalid("Unsupported PSF2 font size"));
+            }
+            if charsize != height * width.div_ceil(8) {
+                return Err(invalid("Inconsistent PSF2 glyph size"));
+            }
+            (width, height, charcount, header_size as usize)
+        } else {
+            return Err(invalid("Not a PSF font"));
+        };
+
+        let size = width.div_ceil(8) as usize * height as usize * charcount as usize;
+        let data = data_start.checked_add(size)
+            .and_then(|end| bytes.get(data_start..end))
+            .ok_or_else(|| invalid("Truncated PSF glyph data"))?;
+        Font::new(width, height, charcount, data.to_vec())
+    }
+
+    /// Returns the width of the glyphs, in pixels.
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Returns the height of the glyphs, in pixels.
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Returns the number of glyphs.
+    pub fn charcount(&self) -> u32 {
+        self.charcount
+    }
+
+    /// Returns the data of the given glyph, if present.
+    pub fn glyph(&self, index: u32) -> Option<&[u8]> {
+        if index >= self.charcount {
+            return None;
+        }
+        let size = self.glyph_size();
+        Some(&self.data[index as usize * size..(index as usize + 1) * size])
+    }
+
+    /// Returns the raw glyph data.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    fn pitch(&self) -> usize {
+        (self.width as usize).div_ceil(8)
+    }
+
+    fn glyph_size(&self) -> usize {
+        self.pitch() * self.height as usize
+    }
+
+}
+
+/// An entry of a [`UnicodeMap`], mapping a unicode code point to the glyph displaying it.
+/// 
+/// [`UnicodeMap`]: crate::UnicodeMap
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct UnicodeMapEntry {
+    /// Unicode code point. The kernel only supports the Basic Multilingual Plane.
+    pub unicode: u16,
+    /// Position of the glyph in the font.
+    pub glyph: u16
+}
+
+impl UnicodeMapEntry {
+
+    /// Returns the character of this entry, or `None` if the code point is not a valid character.
+    pub fn char(&self) -> Option<char> {
+        std::char::from_u32(self.unicode.into())
+    }
+
+}
+
+/// Map telling the kernel which glyph of the font to use to display each unicode character.
+/// 
+/// Each character is mapped to a single glyph, while the same glyph can display multiple characters.
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct UnicodeMap {
+    entries: Vec<UnicodeMapEntry>
+}
+
+impl UnicodeMap {
+
+    /// Creates a new empty map.
+    pub fn new() -> UnicodeMap {
+        UnicodeMap::default()
+    }
+
+    /// Returns the number of entries of the map.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns whether the map is empty.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns an iterator over the entries of the map.
+    pub fn iter(&self) -> impl Iterator<Item = &UnicodeMapEntry> {
+        self.entries.iter()
+    }
+
+    /// Returns the glyph displaying the given character, if any.
+    pub fn glyph(&self, c: char) -> Option<u16> {
+        self.entries.iter().find(|e| e.unicode as u32 == c as u32).map(|e| e.glyph)
+    }
+
+    /// Maps the given character to the given glyph, replacing any previous mapping of the character.
+    /// 
+    /// Returns an error of kind [`InvalidInput`] if the character is outside of the Basic Multilingual Plane.
+    /// 
+    /// [`InvalidInput`]: std::io::ErrorKind::InvalidInput
+    pub fn insert(&mut self, c: char, glyph: u16) -> Result<()> {
+        if c as u32 > 0xFFFF {
+            return Err(VtError::new(io::ErrorKind::InvalidInput, format!("Character {:?} is outside of the Basic Multilingual Plane", c)));
+        }
+        self.remove(c);
+        self.entries.push(UnicodeMapEntry { unicode: c as u16, glyph });
+        Ok(())
+    }
+
+    /// Removes the mapping of the given character, returning the glyph it was mapped to.
+    pub fn remove(&mut self, c: char) -> Option<u16> {
+        let i = self.entries.iter().position(|e| e.unicode as u32 == c as u32)?;
+        Some(self.entries.remove(i).glyph)
+    }
+
+    /// Retains only the entries for which the given predicate returns `true`.
+    pub fn retain<F: FnMut(&UnicodeMapEntry) -> bool>(&mut self, f: F) {
+        self.entries.retain(f);
+    }
+
+}
+
+impl std::iter::FromIterator<UnicodeMapEntry> for UnicodeMap {
+    fn from_iter<I: IntoIterator<Item = UnicodeMapEntry>>(iter: I) -> UnicodeMap {
+        UnicodeMap {
+            entries: iter.into_iter().collect()
+        }
+    }
+}
+
+const MAX_WIDTH: u32 = 32;
+const MAX_HEIGHT: u32 = 32;
+const MAX_CHARCOUNT: u32 = 512;
+
+const PSF1_MAGIC: [u8; 2] = [0x36, 0x04];
+const PSF1_MODE512: u8 = 0x01;
+const PSF2_MAGIC: [u8; 4] = [0x72, 0xB5, 0x4A, 0x86];
+
+impl<'a> Vt<'a> {
+
+    /// Returns the font currently loaded in this terminal.
+    pub fn font(&self) -> Result<Font> {
+
+        // Ask for the maximum size supported by the kernel: 512 glyphs of 32x32 pixels
+        let mut data = vec![0u8; 512 * 32 * 4];
+        let mut op = ffi::ConsoleFontOp {
+            op: ffi::KD_FONT_OP_GET,
+            flags: 0,
+            width: 32,
+            height: 32,
+            charcount: 512,
+            data: data.as_mut_ptr()
+        };
+        ffi::kdfontop(self.as_raw_fd(), &mut op)?;
+
+        // Remove the padding to 32 rows from every glyph
+        let pitch = op.width.div_ceil(8) as usize;
+        let glyph_size = pitch * op.height as usize;
+        let mut glyphs = Vec::with_capacity(glyph_size * op.charcount as usize);
+        for i in 0..op.charcount as usize {
+            let start = i * 32 * pitch;
+            glyphs.extend_from_slice(&data[start..start + glyph_size]);
+        }
+
+        Font::new(op.width, op.height, op.charcount, glyphs)
+    }
+
+    /// Loads the given font in this terminal.
+    /// 
+    /// Returns `self` for chaining.
+    pub fn set_font(&mut self, font: &Font) -> Result<&mut Self> {
+
+        // The kernel expects each glyph to be padded to 32 rows
+        let pitch = font.pitch();
+        let mut data = vec![0u8; font.charcount as usize * 32 * pitch];
+        for i in 0..font.charcount {
+            let glyph = font.glyph(i).unwrap();
+            let start = i as usize * 32 * pitch;
+            data[start..start + glyph.len()].copy_from_slice(glyph);
+        }
+
+        let mut op = ffi::ConsoleFontOp {
+            op: ffi::KD_FONT_OP_SET,
+            flags: 0,
+            width: font.width,
+            height: font.height,
+            charcount: font.charcount,
+            data: data.as_mut_ptr()
+        };
+        ffi::kdfontop(self.as_raw_fd(), &mut op)?;
+
+        Ok(self)
+    }
+
+    /// Returns the unicode map of the font of this terminal.
+    pub fn unicode_map(&self) -> Result<UnicodeMap> {
+        let mut entries = vec![ffi::UniPair::default(); 512];
+        loop {
+            let mut desc = ffi::UnimapDesc {
+                entry_ct: entries.len() as u16,
+                entries: entries.as_mut_ptr()
+            };
+            match ffi::gio_unimap(self.as_raw_fd(), &mut desc) {
+                Ok(()) => {
+                    entries.truncate(desc.entry_ct as usize);
+                    break;
+                },
+
+                // The buffer was too small, and the kernel told us the right size
+                Err(VtError::Ioctl { errno: nix::libc::ENOMEM, .. }) if desc.entry_ct as usize > entries.len() => {
+                    entries.resize(desc.entry_ct as usize, ffi::UniPair::default());
+                },
+                
+                Err(e) => return Err(e)
+            }
+        }
+
+        Ok(entries.into_iter().map(|e| UnicodeMapEntry { unicode: e.unicode, glyph: e.fontpos }).collect())
+    }
+
+    /// Replaces the unicode map of the font of this terminal.
+    /// 
+    /// Returns `self` for chaining.
+    pub fn set_unicode_map(&mut self, map: &UnicodeMap) -> Result<&mut Self> {
+        let init = ffi::UnimapInit {
+            advised_hashsize: 0,
+            advised_hashstep: 0,
+            advised_hashlevel: 0
+        };
+        if map.len() > u16::MAX as usize {
+            return Err(VtError::new(io::ErrorKind::InvalidInput, "Too many entries in the unicode map"));
+        }
+        ffi::pio_unimapclr(self.as_raw_fd(), &init)?;
+
+        let mut entries: Vec<ffi::UniPair> = map.iter().map(|e| ffi::UniPair { unicode: e.unicode, fontpos: e.glyph }).collect();
+        let desc = ffi::UnimapDesc {
+            entry_ct: entries.len() as u16,
+            entries: entries.as_mut_ptr()
+        };
+        ffi::pio_unimap(self.as_raw_fd(), &desc)?;
+
+        Ok(self)
+    }
+
+    /// Returns the number of font slots available to this terminal.
+    /// 
+    /// Even if some video hardware can hold multiple fonts at once, the kernel only exposes
+    /// a single font per terminal through `KDFONTOP`, so this is always `1`.
+    pub fn font_slot_count(&self) -> Result<u8> {
+        Ok(1)
+    }
+
+    /// Loads the given font in the given slot. Since the kernel only supports a single slot
+    /// (see [`Vt::font_slot_count`]), slot `0` is equivalent to [`Vt::set_font`],
+    /// while any other slot results in an error of kind [`Unsupported`].
+    /// 
+    /// Returns `self` for chaining.
+    /// 
+    /// [`Vt::font_slot_count`]: crate::Vt::font_slot_count
+    /// [`Vt::set_font`]: crate::Vt::set_font
+    /// [`Unsupported`]: std::io::ErrorKind::Unsupported
+    pub fn set_font_in_slot(&mut self, slot: u8, font: &Font) -> Result<&mut Self> {
+        self.check_font_slot(slot)?;
+        self.set_font(font)
+    }
+
+    /// Makes the font in the given slot the active one. Since the kernel only supports a single slot
+    /// (see [`Vt::font_slot_count`]), this does nothing for slot `0`,
+    /// and results in an error of kind [`Unsupported`] for any other slot.
+    /// 
+    /// Returns `self` for chaining.
+    /// 
+    /// [`Vt::font_slot_count`]: crate::Vt::font_slot_count
+    /// [`Unsupported`]: std::io::ErrorKind::Unsupported
+    pub fn activate_font_slot(&mut self, slot: u8) -> Result<&mut Self> {
+        self.check_font_slot(slot)?;
+        Ok(self)
+    }
+
+    fn check_font_slot(&self, slot: u8) -> Result<()> {
+        if slot < self.font_slot_count()? {
+            Ok(())
+        } else {
+            Err(VtError::new(io::ErrorKind::Unsupported, format!("Font slot {} is not supported", slot)))
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn psf2(header_size: u32, charcount: u32, charsize: u32, height: u32, width: u32, data_len: usize) -> Vec<u8> {
+        let mut bytes = PSF2_MAGIC.to_vec();
+        for field in &[0, header_size, 0, charcount, charsize, height, width] {
+            bytes.extend_from_slice(&field.to_le_bytes());
+        }
+        bytes.resize(header_size as usize, 0);
+        bytes.extend(vec![0xAA; data_len]);
+        bytes
+    }
+
+    fn kind(res: Result<Font>) -> io::ErrorKind {
+        res.unwrap_err().kind()
+    }
+
+    #[test]
+    fn parses_psf1() {
+        let mut bytes = vec![0x36, 0x04, 0, 16];
+        bytes.extend(vec![0x55; 256 * 16]);
+        let font = Font::from_psf_bytes(&bytes).unwrap();
+        assert_eq!((font.width(), font.height(), font.charcount()), (8, 16, 256));
+        assert_eq!(font.glyph(255).unwrap(), &[0x55; 16][..]);
+    }
+
+    #[test]
+    fn parses_psf2() {
+        let bytes = psf2(32, 256, 32, 16, 10, 256 * 32);
+        let font = Font::from_psf_bytes(&bytes).unwrap();
+        assert_eq!((font.width(), font.height(), font.charcount()), (10, 16, 256));
+        assert_eq!(font.data().len(), 256 * 32);
+    }
+
+    #[test]
+    fn rejects_truncated_fonts() {
+        assert_eq!(kind(Font::from_psf_bytes(&[0x36, 0x04, 0])), io::ErrorKind::InvalidData);
+        assert_eq!(kind(Font::from_psf_bytes(&PSF2_MAGIC)), io::ErrorKind::InvalidData);
+        assert_eq!(kind(Font::from_psf_bytes(&psf2(32, 256, 16, 16, 8, 256 * 16 - 1))), io::ErrorKind::InvalidData);
+        assert_eq!(kind(Font::from_psf_bytes(b"not a font")), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn rejects_oversized_headers() {
+        let huge = psf2(32, u32::MAX, 0x8000_0000, 0x8000_0000, u32::MAX, 0);
+        assert_eq!(kind(Font::from_psf_bytes(&huge)), io::ErrorKind::InvalidData);
+        assert_eq!(kind(Font::from_psf_bytes(&psf2(32, 513, 16, 16, 8, 0))), io::ErrorKind::InvalidData);
+        assert_eq!(kind(Font::from_psf_bytes(&psf2(32, 256, 0, 0, 8, 0))), io::ErrorKind::InvalidData);
+
+        // A header size close to the maximum must not wrap around when added to the data size
+        let mut wrapping = psf2(32, 256, 16, 16, 8, 256 * 16);
+        wrapping[8..12].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(kind(Font::from_psf_bytes(&wrapping)), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn rejects_inconsistent_headers() {
+        assert_eq!(kind(Font::from_psf_bytes(&psf2(32, 256, 17, 16, 8, 256 * 17))), io::ErrorKind::InvalidData);
+    }
+
+}
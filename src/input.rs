@@ -0,0 +1,432 @@
+/// Maximum length of a CSI escape sequence: longer sequences are considered garbage.
+const MAX_CSI_LENGTH: usize = 32;
+
+/// A key decoded from the input of a terminal.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub enum Key {
+    /// A key producing a character. Control combinations like `Ctrl+A` are reported
+    /// as the lowercase letter together with [`KeyModifiers::CTRL`].
+    /// 
+    /// [`KeyModifiers::CTRL`]: crate::KeyModifiers::CTRL
+    Char(char),
+    Enter,
+    Tab,
+    Backspace,
+    Escape,
+    Up,
+    Down,
+    Left,
+    Right,
+    Home,
+    End,
+    Insert,
+    Delete,
+    PageUp,
+    PageDown,
+    /// The center key of the keypad (`5`), when NumLock is off.
+    Begin,
+    /// A function key, numbered from 1.
+    F(u8),
+    /// A sequence of bytes that could not be decoded.
+    Unknown(Vec<u8>)
+}
+
+bitflags! {
+    /// Modifiers pressed together with a [`Key`].
+    /// 
+    /// The Linux console reports only the modifiers encoded in the byte stream:
+    /// `Alt` is reported for keys prefixed by `ESC`, and `Ctrl` for control characters.
+    /// For the full modifier state, see [`Vt::shift_state`].
+    /// 
+    /// [`Key`]: crate::Key
+    /// [`Vt::shift_state`]: crate::Vt::shift_state
+    pub struct KeyModifiers: u8 {
+        const SHIFT = 1;
+        const ALT   = 1 << 1;
+        const CTRL  = 1 << 2;
+    }
+}
+
+/// A keypress decoded from the input of a terminal.
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct KeyEvent {
+    pub key: Key,
+    pub modifiers: KeyModifiers
+}
+
+impl KeyEvent {
+
+    /// Creates a new event for the given key and modifiers.
+    pub fn new(key: Key, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent {
+            key,
+            modifiers
+        }
+    }
+
+    fn plain(key: Key) -> KeyEvent {
+        KeyEvent::new(key, KeyModifiers::empty())
+    }
+
+}
+
+/// Incremental decoder turning the bytes produced by a terminal in translated (`K_XLATE`)
+/// or Unicode (`K_UNICODE`) keyboard mode into [`KeyEvent`]s.
+/// 
+/// Bytes can be pushed as they are read, even if escape sequences are split across reads:
+/// incomplete sequences are kept until the rest of the bytes arrive.
+/// Since a lone `ESC` cannot be told apart from the beginning of a sequence,
+/// [`KeyDecoder::flush`] should be called when no more input arrives within a short time.
+/// [`Vt::read_key_event`] takes care of all of this for blocking reads.
+/// 
+/// [`KeyEvent`]: crate::KeyEvent
+/// [`KeyDecoder::flush`]: crate::KeyDecoder::flush
+/// [`Vt::read_key_event`]: crate::Vt::read_key_event
+#[derive(Clone, Debug, Default)]
+pub struct KeyDecoder {
+    buf: Vec<u8>
+}
+
+impl KeyDecoder {
+
+    /// Creates a new empty decoder.
+    pub fn new() -> KeyDecoder {
+        KeyDecoder::default()
+    }
+
+    /// Appends some bytes read from the terminal to the decoder.
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    /// Returns the next complete event, or `None` if more bytes are needed.
+    pub fn next_event(&mut self) -> Option<KeyEvent> {
+        let (event, n) = decode(&self.buf, false)?;
+        self.buf.drain(..n);
+        Some(event)
+    }
+
+    /// Returns the next event, decoding any pending incomplete sequence as it is,
+    /// or `None` if the decoder is empty. A pending lone `ESC` is reported as [`Key::Escape`].
+    /// 
+    /// [`Key::Escape`]: crate::Key::Escape
+    pub fn flush(&mut self) -> Option<KeyEvent> {
+        let (event, n) = decode(&self.buf, true)?;
+        self.buf.drain(..n);
+        Some(event)
+    }
+
+    /// Returns `true` if there are bytes waiting to be decoded.
+    pub fn has_pending(&self) -> bool {
+        !self.buf.is_empty()
+    }
+
+}
+
+/// Decodes the first event in `buf`, returning it together with the number of bytes it spans.
+/// 
+/// If `buf` holds an incomplete sequence, `None` is returned, unless `complete` is set:
+/// in that case, the bytes are decoded as well as possible, since no more are going to arrive.
+/// `None` is always returned for an empty buffer.
+pub(crate) fn decode(buf: &[u8], complete: bool) -> Option<(KeyEvent, usize)> {
+    let first = *buf.first()?;
+    if first != 0x1b {
+        return decode_plain(buf, complete);
+    }
+
+    match buf.get(1) {
+        None if complete => Some((KeyEvent::plain(Key::Escape), 1)),
+        None => None,
+        Some(b'[') => match decode_csi(buf) {
+            Some(res) => Some(res),
+            None if complete && buf.len() == 2 => Some((KeyEvent::new(Key::Char('['), KeyModifiers::ALT), 2)),
+            None if complete => Some((KeyEvent::plain(Key::Unknown(buf.to_vec())), buf.len())),
+            None => None
+        },
+        Some(b'O') => match buf.get(2) {
+            Some(&b) => Some((decode_ss3(b), 3)),
+            None if complete => Some((KeyEvent::new(Key::Char('O'), KeyModifiers::ALT), 2)),
+            None => None
+        },
+        Some(_) => {
+            // The console sends Alt+key as ESC followed by the key
+            let (mut event, n) = decode(&buf[1..], complete)?;
+            event.modifiers |= KeyModifiers::ALT;
+            Some((event, n + 1))
+        }
+    }
+}
+
+fn decode_plain(buf: &[u8], complete: bool) -> Option<(KeyEvent, usize)> {
+    let first = buf[0];
+    let event = match first {
+        b'\r' | b'\n' => KeyEvent::plain(Key::Enter),
+        b'\t' => KeyEvent::plain(Key::Tab),
+        0x08 | 0x7f => KeyEvent::plain(Key::Backspace),
+        0x00 => KeyEvent::new(Key::Char(' '), KeyModifiers::CTRL),
+        0x01..=0x1a => KeyEvent::new(Key::Char((b'a' + first - 1) as char), KeyModifiers::CTRL),
+        0x1c..=0x1f => KeyEvent::new(Key::Char((first + 0x40) as char), KeyModifiers::CTRL),
+        0x20..=0x7e => KeyEvent::plain(Key::Char(first as char)),
+        _ => return decode_utf8(buf, complete)
+    };
+    Some((event, 1))
+}
+
+fn decode_utf8(buf: &[u8], complete: bool) -> Option<(KeyEvent, usize)> {
+    let len = match buf[0] {
+        0xc2..=0xdf => 2,
+        0xe0..=0xef => 3,
+        0xf0..=0xf4 => 4,
+        _ => 0
+    };
+    if len > 0 && buf.len() < len && !complete {
+        return None;
+    }
+    match buf.get(..len).and_then(|b| std::str::from_utf8(b).ok()).and_then(|s| s.chars().next()) {
+        Some(c) => Some((KeyEvent::plain(Key::Char(c)), len)),
+        None => Some((KeyEvent::plain(Key::Unknown(vec![buf[0]])), 1))
+    }
+}
+
+/// Decodes a sequence starting with `ESC [`, returning `None` if it is incomplete.
+fn decode_csi(buf: &[u8]) -> Option<(KeyEvent, usize)> {
+    // The Linux console sends F1-F5 as ESC [ [ A-E
+    if buf.get(2) == Some(&b'[') {
+        let b = *buf.get(3)?;
+        let key = match b {
+            b'A'..=b'E' => Key::F(b - b'A' + 1),
+            _ => Key::Unknown(buf[..4].to_vec())
+        };
+        return Some((KeyEvent::plain(key), 4));
+    }
+
+    // Parameters are followed by a single final byte
+    let end = match buf[2..].iter().position(|b| !(0x30..=0x3f).contains(b)) {
+        Some(i) => i + 2,
+        None if buf.len() >= MAX_CSI_LENGTH => return Some((KeyEvent::plain(Key::Unknown(buf.to_vec())), buf.len())),
+        None => return None
+    };
+    let len = end + 1;
+    let unknown = || Some((KeyEvent::plain(Key::Unknown(buf[..len].to_vec())), len));
+
+    let params: Vec<Option<u32>> = std::str::from_utf8(&buf[2..end]).ok()?
+        .split(';')
+        .map(|p| p.parse().ok())
+        .collect();
+    let param = |i: usize| params.get(i).cloned().flatten();
+
+    // xterm-style modifiers are encoded as 1 + bitmask in the second parameter
+    let modifiers = match param(1) {
+        Some(m) if m >= 1 => KeyModifiers::from_bits_truncate(((m - 1) & 0x7) as u8),
+        _ => KeyModifiers::empty()
+    };
+
+    let key = match buf[end] {
+        b'A' => Key::Up,
+        b'B' => Key::Down,
+        b'C' => Key::Right,
+        b'D' => Key::Left,
+        b'H' => Key::Home,
+        b'F' => Key::End,
+        b'G' => Key::Begin,
+        b'P' => Key::F(1),
+        b'Q' => Key::F(2),
+        b'R' => Key::F(3),
+        b'S' => Key::F(4),
+        b'Z' => return Some((KeyEvent::new(Key::Tab, KeyModifiers::SHIFT), len)),
+        b'~' => match param(0) {
+            Some(1) | Some(7) => Key::Home,
+            Some(2) => Key::Insert,
+            Some(3) => Key::Delete,
+            Some(4) | Some(8) => Key::End,
+            Some(5) => Key::PageUp,
+            Some(6) => Key::PageDown,
+            Some(n @ 11..=15) => Key::F((n - 10) as u8),
+            Some(n @ 17..=21) => Key::F((n - 11) as u8),
+            Some(n @ 23..=26) => Key::F((n - 12) as u8),
+            Some(n @ 28..=29) => Key::F((n - 13) as u8),
+            Some(n @ 31..=34) => Key::F((n - 14) as u8),
+            _ => return unknown()
+        },
+        _ => return unknown()
+    };
+    Some((KeyEvent::new(key, modifiers), len))
+}
+
+/// Decodes the final byte of a sequence starting with `ESC O`, sent in application cursor mode.
+fn decode_ss3(b: u8) -> KeyEvent {
+    let key = match b {
+        b'A' => Key::Up,
+        b'B' => Key::Down,
+        b'C' => Key::Right,
+        b'D' => Key::Left,
+        b'H' => Key::Home,
+        b'F' => Key::End,
+        b'M' => Key::Enter,
+        b'P'..=b'S' => Key::F(b - b'P' + 1),
+        _ => Key::Unknown(vec![0x1b, b'O', b])
+    };
+    KeyEvent::plain(key)
+}
+
+/// A key press or release decoded from the input of a terminal in medium raw (`K_MEDIUMRAW`) keyboard mode.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct RawKeyEvent {
+    /// Linux keycode of the key, as defined in `linux/input-event-codes.h`.
+    pub keycode: u16,
+    /// `true` if the key has been pressed, `false` if it has been released.
+    pub pressed: bool
+}
+
+/// Incremental decoder turning the bytes produced by a terminal in medium raw (`K_MEDIUMRAW`)
+/// keyboard mode into [`RawKeyEvent`]s.
+/// 
+/// In this mode, each key press or release is sent as its keycode, with the high bit set for releases.
+/// Keycodes above 127 are sent as a 0 byte followed by two bytes containing the actual keycode:
+/// bytes can be pushed as they are read, and split sequences are kept until complete.
+/// [`Vt::read_raw_key_event`] takes care of this for blocking reads.
+/// 
+/// [`RawKeyEvent`]: crate::RawKeyEvent
+/// [`Vt::read_raw_key_event`]: crate::Vt::read_raw_key_event
+#[derive(Clone, Debug, Default)]
+pub struct MediumRawDecoder {
+    buf: Vec<u8>
+}
+
+impl MediumRawDecoder {
+
+    /// Creates a new empty decoder.
+    pub fn new() -> MediumRawDecoder {
+        MediumRawDecoder::default()
+    }
+
+    /// Appends some bytes read from the terminal to the decoder.
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    /// Returns the next complete event, or `None` if more bytes are needed.
+    pub fn next_event(&mut self) -> Option<RawKeyEvent> {
+        let (event, n) = decode_mediumraw(&self.buf)?;
+        self.buf.drain(..n);
+        Some(event)
+    }
+
+}
+
+/// Decodes the first medium raw event in `buf`, returning it together with the number of bytes it spans,
+/// or `None` if `buf` is empty or holds an incomplete sequence.
+pub(crate) fn decode_mediumraw(buf: &[u8]) -> Option<(RawKeyEvent, usize)> {
+    let first = *buf.first()?;
+    let pressed = first & 0x80 == 0;
+    if first & 0x7f != 0 {
+        return Some((RawKeyEvent { keycode: (first & 0x7f) as u16, pressed }, 1));
+    }
+
+    let high = *buf.get(1)?;
+    let low = *buf.get(2)?;
+    let keycode = (((high & 0x7f) as u16) << 7) | (low & 0x7f) as u16;
+    Some((RawKeyEvent { keycode, pressed }, 3))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(key: Key, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent::new(key, modifiers)
+    }
+
+    fn plain(key: Key) -> KeyEvent {
+        KeyEvent::plain(key)
+    }
+
+    fn raw(keycode: u16, pressed: bool) -> RawKeyEvent {
+        RawKeyEvent { keycode, pressed }
+    }
+
+    #[test]
+    fn decodes_plain_keys() {
+        assert_eq!(decode(b"a", false), Some((plain(Key::Char('a')), 1)));
+        assert_eq!(decode(b"\r", false), Some((plain(Key::Enter), 1)));
+        assert_eq!(decode(b"\x7f", false), Some((plain(Key::Backspace), 1)));
+        assert_eq!(decode(b"\x03", false), Some((event(Key::Char('c'), KeyModifiers::CTRL), 1)));
+        assert_eq!(decode("é!".as_bytes(), false), Some((plain(Key::Char('é')), 2)));
+        assert_eq!(decode(b"", true), None);
+    }
+
+    #[test]
+    fn decodes_escape_sequences() {
+        assert_eq!(decode(b"\x1b[A", false), Some((plain(Key::Up), 3)));
+        assert_eq!(decode(b"\x1b[3~x", false), Some((plain(Key::Delete), 4)));
+        assert_eq!(decode(b"\x1b[[A", false), Some((plain(Key::F(1)), 4)));
+        assert_eq!(decode(b"\x1b[24~", false), Some((plain(Key::F(12)), 5)));
+        assert_eq!(decode(b"\x1bOP", false), Some((plain(Key::F(1)), 3)));
+        assert_eq!(decode(b"\x1b[1;5C", false), Some((event(Key::Right, KeyModifiers::CTRL), 6)));
+        assert_eq!(decode(b"\x1b[Z", false), Some((event(Key::Tab, KeyModifiers::SHIFT), 3)));
+        assert_eq!(decode(b"\x1b[99~", false), Some((plain(Key::Unknown(b"\x1b[99~".to_vec())), 5)));
+    }
+
+    #[test]
+    fn waits_for_partial_sequences() {
+        for partial in &[&b"\x1b"[..], b"\x1b[", b"\x1b[1", b"\x1b[1;5", b"\x1b[[", b"\x1bO", b"\xc3", b"\x1b\x1b["] {
+            assert_eq!(decode(partial, false), None, "{:?}", partial);
+        }
+
+        let mut decoder = KeyDecoder::new();
+        decoder.push(b"\x1b[1");
+        assert_eq!(decoder.next_event(), None);
+        decoder.push(b"5~");
+        assert_eq!(decoder.next_event(), Some(plain(Key::F(5))));
+        assert!(!decoder.has_pending());
+    }
+
+    #[test]
+    fn decodes_partial_sequences_at_eof() {
+        assert_eq!(decode(b"\x1b", true), Some((plain(Key::Escape), 1)));
+        assert_eq!(decode(b"\x1b[", true), Some((event(Key::Char('['), KeyModifiers::ALT), 2)));
+        assert_eq!(decode(b"\x1bO", true), Some((event(Key::Char('O'), KeyModifiers::ALT), 2)));
+        assert_eq!(decode(b"\x1b[1;", true), Some((plain(Key::Unknown(b"\x1b[1;".to_vec())), 4)));
+        assert_eq!(decode(b"\xc3", true), Some((plain(Key::Unknown(vec![0xc3])), 1)));
+
+        let mut decoder = KeyDecoder::new();
+        decoder.push(b"\x1b");
+        assert_eq!(decoder.next_event(), None);
+        assert_eq!(decoder.flush(), Some(plain(Key::Escape)));
+        assert_eq!(decoder.flush(), None);
+    }
+
+    #[test]
+    fn decodes_alt_prefixed_keys() {
+        assert_eq!(decode(b"\x1bx", false), Some((event(Key::Char('x'), KeyModifiers::ALT), 2)));
+        assert_eq!(decode(b"\x1b\x01", false), Some((event(Key::Char('a'), KeyModifiers::CTRL | KeyModifiers::ALT), 2)));
+        assert_eq!(decode(b"\x1b\x1b[A", false), Some((event(Key::Up, KeyModifiers::ALT), 4)));
+        assert_eq!(decode(b"\x1b\x1b", true), Some((event(Key::Escape, KeyModifiers::ALT), 2)));
+    }
+
+    #[test]
+    fn decodes_mediumraw() {
+        assert_eq!(decode_mediumraw(&[30]), Some((raw(30, true), 1)));
+        assert_eq!(decode_mediumraw(&[0x80 | 30]), Some((raw(30, false), 1)));
+        assert_eq!(decode_mediumraw(&[]), None);
+    }
+
+    #[test]
+    fn decodes_mediumraw_long_keycodes() {
+        // Keycode 300 = 2 << 7 | 44
+        assert_eq!(decode_mediumraw(&[0x00, 0x82, 0xac]), Some((raw(300, true), 3)));
+        assert_eq!(decode_mediumraw(&[0x80, 0x82, 0xac, 30]), Some((raw(300, false), 3)));
+        assert_eq!(decode_mediumraw(&[0x00, 0x81, 0x80]), Some((raw(128, true), 3)));
+        assert_eq!(decode_mediumraw(&[0x00]), None);
+        assert_eq!(decode_mediumraw(&[0x00, 0x82]), None);
+
+        let mut decoder = MediumRawDecoder::new();
+        decoder.push(&[30, 0x80]);
+        assert_eq!(decoder.next_event(), Some(raw(30, true)));
+        assert_eq!(decoder.next_event(), None);
+        decoder.push(&[0x82, 0xac, 0x80 | 30]);
+        assert_eq!(decoder.next_event(), Some(raw(300, false)));
+        assert_eq!(decoder.next_event(), Some(raw(30, false)));
+    }
+
+}
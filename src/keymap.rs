@@ -0,0 +1,277 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::os::unix::io::AsRawFd;
+use crate::ffi;
+use crate::error::{Result, VtError};
+use crate::vt::{Vt, ShiftState};
+
+/// Value of an empty keymap entry, for keys that produce nothing.
+pub const KEY_HOLE: u16 = ffi::K_HOLE;
+
+/// Value of a keymap entry triggering the Secure Attention Key (see [`Console::trigger_sak`]).
+/// 
+/// [`Console::trigger_sak`]: crate::Console::trigger_sak
+pub const KEY_SAK: u16 = ffi::K_SAK;
+
+/// A keyboard map, translating keycodes to actions depending on the modifiers held down.
+/// 
+/// A keymap is made of up to 256 tables, one for each combination of [`ShiftState`] modifiers,
+/// each one mapping the 256 keycodes to a value. As defined in `linux/keyboard.h`, the high byte of a value
+/// is the type of action (like `KT_LATIN` for characters or `KT_FN` for function keys)
+/// and the low byte is the action itself. Entries of missing tables produce nothing.
+/// 
+/// A keymap also contains the strings sent by function keys, indexed by the low byte
+/// of their `KT_FN` value (0 for `F1`), and the [`Diacritic`] combinations used to compose accented characters.
+/// 
+/// Use [`Vt::keymap`] to read the keymap in use by the kernel, and [`Vt::set_keymap`] to apply a modified one.
+/// 
+/// [`ShiftState`]: crate::ShiftState
+/// [`Diacritic`]: crate::Diacritic
+/// [`Vt::keymap`]: crate::Vt::keymap
+/// [`Vt::set_keymap`]: crate::Vt::set_keymap
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct Keymap {
+    tables: BTreeMap<ShiftState, Vec<u16>>,
+    strings: BTreeMap<u8, Vec<u8>>,
+    diacritics: Vec<Diacritic>
+}
+
+/// An entry of the accent table, used to compose accented characters with dead keys or the `Compose` key.
+/// 
+/// When `accent` is typed followed by `base`, the kernel produces `result` instead.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Diacritic {
+    pub accent: char,
+    pub base: char,
+    pub result: char
+}
+
+impl Keymap {
+
+    /// Creates a new empty keymap, without any table.
+    pub fn new() -> Keymap {
+        Keymap::default()
+    }
+
+    /// Returns the value of the given key when pressed with the given modifiers,
+    /// or `None` if the keymap has no table for the modifiers.
+    pub fn get(&self, modifiers: ShiftState, keycode: u8) -> Option<u16> {
+        self.tables.get(&modifiers).map(|t| t[keycode as usize])
+    }
+
+    /// Sets the value of the given key when pressed with the given modifiers.
+    /// The table for the modifiers is created if missing, with all the other entries empty.
+    /// 
+    /// Returns `self` for chaining.
+    pub fn set(&mut self, modifiers: ShiftState, keycode: u8, value: u16) -> &mut Self {
+        self.tables.entry(modifiers).or_insert_with(|| vec![KEY_HOLE; ffi::NR_KEYS])[keycode as usize] = value;
+        self
+    }
+
+    /// Returns an iterator over the modifier combinations this keymap has a table for.
+    pub fn tables(&self) -> impl Iterator<Item = ShiftState> + '_ {
+        self.tables.keys().cloned()
+    }
+
+    /// Returns the string sent by the given function key, if the keymap defines one.
+    pub fn string(&self, func: u8) -> Option<&[u8]> {
+        self.strings.get(&func).map(|s| s.as_slice())
+    }
+
+    /// Sets the string sent by the given function key.
+    /// 
+    /// Returns `self` for chaining.
+    pub fn set_string(&mut self, func: u8, string: &[u8]) -> &mut Self {
+        self.strings.insert(func, string.to_vec());
+        self
+    }
+
+    /// Returns an iterator over the function keys this keymap has a string for, together with the strings.
+    pub fn strings(&self) -> impl Iterator<Item = (u8, &[u8])> + '_ {
+        self.strings.iter().map(|(f, s)| (*f, s.as_slice()))
+    }
+
+    /// Returns the accent table of this keymap.
+    pub fn diacritics(&self) -> &[Diacritic] {
+        &self.diacritics
+    }
+
+    /// Adds an entry to the accent table, replacing any previous entry for the same accent and base.
+    /// 
+    /// Returns `self` for chaining.
+    pub fn add_diacritic(&mut self, diacritic: Diacritic) -> &mut Self {
+        self.diacritics.retain(|d| d.accent != diacritic.accent || d.base != diacritic.base);
+        self.diacritics.push(diacritic);
+        self
+    }
+
+}
+
+impl<'a> Vt<'a> {
+
+    /// Returns the value the kernel associates to the given key when pressed with the given modifiers.
+    /// 
+    /// The returned value is encoded as described in [`Keymap`]. If the kernel has no table
+    /// for the given modifiers, [`KEY_HOLE`] is returned.
+    /// Note that the keymap is shared by all the virtual terminals.
+    /// 
+    /// [`Keymap`]: crate::Keymap
+    /// [`KEY_HOLE`]: crate::KEY_HOLE
+    pub fn keymap_entry(&self, modifiers: ShiftState, keycode: u8) -> Result<u16> {
+        let value = self.read_keymap_entry(modifiers, keycode)?;
+        Ok(if value == ffi::K_NOSUCHMAP { KEY_HOLE } else { value })
+    }
+
+    /// Changes the value the kernel associates to the given key when pressed with the given modifiers,
+    /// allocating the table for the modifiers if needed.
+    /// Since the keymap is shared by all the virtual terminals, this requires the `CAP_SYS_TTY_CONFIG` capability.
+    /// 
+    /// Returns `self` for chaining.
+    pub fn set_keymap_entry(&mut self, modifiers: ShiftState, keycode: u8, value: u16) -> Result<&mut Self> {
+        let entry = ffi::KbEntry {
+            kb_table: modifiers.bits(),
+            kb_index: keycode,
+            kb_value: value
+        };
+        ffi::kdskbent(self.as_raw_fd(), &entry)?;
+        Ok(self)
+    }
+
+    /// Returns the string sent by the given function key, indexed by the low byte of its `KT_FN` value
+    /// (0 for `F1`, see [`Keymap`]). An empty string is returned if the key has no string.
+    /// 
+    /// [`Keymap`]: crate::Keymap
+    pub fn function_key_string(&self, func: u8) -> Result<Vec<u8>> {
+        let mut entry = ffi::KbSEntry {
+            kb_func: func,
+            kb_string: [0; 512]
+        };
+        ffi::kdgkbsent(self.as_raw_fd(), &mut entry)?;
+        let len = entry.kb_string.iter().position(|b| *b == 0).unwrap_or(entry.kb_string.len());
+        Ok(entry.kb_string[..len].to_vec())
+    }
+
+    /// Changes the string sent by the given function key, so that pressing it produces `string` as input.
+    /// The string cannot contain NUL bytes and must be shorter than 512 bytes,
+    /// otherwise an error of kind [`InvalidInput`] is returned.
+    /// Like the rest of the keymap, this requires the `CAP_SYS_TTY_CONFIG` capability.
+    /// 
+    /// Returns `self` for chaining.
+    /// 
+    /// [`InvalidInput`]: std::io::ErrorKind::InvalidInput
+    pub fn set_function_key_string(&mut self, func: u8, string: &[u8]) -> Result<&mut Self> {
+        let mut entry = ffi::KbSEntry {
+            kb_func: func,
+            kb_string: [0; 512]
+        };
+        if string.len() >= entry.kb_string.len() || string.contains(&0) {
+            return Err(VtError::new(io::ErrorKind::InvalidInput, "Function key strings must be shorter than 512 bytes and cannot contain NUL"));
+        }
+        entry.kb_string[..string.len()].copy_from_slice(string);
+        ffi::kdskbsent(self.as_raw_fd(), &entry)?;
+        Ok(self)
+    }
+
+    /// Returns the accent table used by the kernel to compose accented characters.
+    pub fn diacritics(&self) -> Result<Vec<Diacritic>> {
+        let mut table = ffi::KbDiacrsUc {
+            kb_cnt: 0,
+            kbdiacruc: [Default::default(); ffi::MAX_DIACR]
+        };
+        ffi::kdgkbdiacruc(self.as_raw_fd(), &mut table)?;
+
+        let count = (table.kb_cnt as usize).min(ffi::MAX_DIACR);
+        Ok(table.kbdiacruc[..count].iter()
+            .filter_map(|d| Some(Diacritic {
+                accent: std::char::from_u32(d.diacr)?,
+                base: std::char::from_u32(d.base)?,
+                result: std::char::from_u32(d.result)?
+            }))
+            .collect())
+    }
+
+    /// Replaces the accent table used by the kernel to compose accented characters.
+    /// The table can contain at most 256 entries, otherwise an error of kind [`InvalidInput`] is returned.
+    /// Like the rest of the keymap, this requires the `CAP_SYS_TTY_CONFIG` capability.
+    /// 
+    /// Returns `self` for chaining.
+    /// 
+    /// [`InvalidInput`]: std::io::ErrorKind::InvalidInput
+    pub fn set_diacritics(&mut self, diacritics: &[Diacritic]) -> Result<&mut Self> {
+        if diacritics.len() > ffi::MAX_DIACR {
+            return Err(VtError::new(io::ErrorKind::InvalidInput, format!("Too many diacritics: at most {} are supported", ffi::MAX_DIACR)));
+        }
+        let mut table = ffi::KbDiacrsUc {
+            kb_cnt: diacritics.len() as _,
+            kbdiacruc: [Default::default(); ffi::MAX_DIACR]
+        };
+        for (entry, d) in table.kbdiacruc.iter_mut().zip(diacritics) {
+            entry.diacr = d.accent as _;
+            entry.base = d.base as _;
+            entry.result = d.result as _;
+        }
+        ffi::kdskbdiacruc(self.as_raw_fd(), &table)?;
+        Ok(self)
+    }
+
+    /// Reads the whole keymap in use by the kernel, including only the tables that are allocated,
+    /// the function keys with a non-empty string and the accent table.
+    pub fn keymap(&self) -> Result<Keymap> {
+        let mut keymap = Keymap::new();
+        for table in 0..ffi::MAX_NR_KEYMAPS {
+            let modifiers = ShiftState::from_bits_truncate(table as u8);
+
+            // The kernel reports missing tables only when reading the first entry
+            if self.read_keymap_entry(modifiers, 0)? == ffi::K_NOSUCHMAP {
+                continue;
+            }
+
+            let values = (0..ffi::NR_KEYS)
+                .map(|keycode| self.read_keymap_entry(modifiers, keycode as u8))
+                .collect::<Result<Vec<u16>>>()?;
+            keymap.tables.insert(modifiers, values);
+        }
+        for func in 0..ffi::MAX_NR_FUNC {
+            let string = self.function_key_string(func as u8)?;
+            if !string.is_empty() {
+                keymap.strings.insert(func as u8, string);
+            }
+        }
+        keymap.diacritics = self.diacritics()?;
+        Ok(keymap)
+    }
+
+    /// Writes all the tables and function key strings of the given keymap to the kernel,
+    /// allocating the tables if needed. Tables and strings missing from the keymap are left untouched.
+    /// The accent table is replaced only if the keymap has one.
+    /// This requires the same privileges of [`Vt::set_keymap_entry`].
+    /// 
+    /// Returns `self` for chaining.
+    /// 
+    /// [`Vt::set_keymap_entry`]: crate::Vt::set_keymap_entry
+    pub fn set_keymap(&mut self, keymap: &Keymap) -> Result<&mut Self> {
+        for (modifiers, values) in &keymap.tables {
+            for (keycode, value) in values.iter().enumerate() {
+                self.set_keymap_entry(*modifiers, keycode as u8, *value)?;
+            }
+        }
+        for (func, string) in &keymap.strings {
+            self.set_function_key_string(*func, string)?;
+        }
+        if !keymap.diacritics.is_empty() {
+            self.set_diacritics(&keymap.diacritics)?;
+        }
+        Ok(self)
+    }
+
+    fn read_keymap_entry(&self, modifiers: ShiftState, keycode: u8) -> Result<u16> {
+        let mut entry = ffi::KbEntry {
+            kb_table: modifiers.bits(),
+            kb_index: keycode,
+            kb_value: 0
+        };
+        ffi::kdgkbent(self.as_raw_fd(), &mut entry)?;
+        Ok(entry.kb_value)
+    }
+
+}
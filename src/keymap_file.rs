@@ -0,0 +1,739 @@
+use std::fs;
+use std::convert::TryFrom;
+use std::io;
+use std::path::{Path, PathBuf};
+use crate::error::{Result, VtError};
+use crate::keymap::{Keymap, Diacritic};
+use crate::vt::{Vt, ShiftState};
+
+/// Maximum nesting of `include` statements, to protect against include loops.
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// Standard directories searched for included files.
+const INCLUDE_DIRS: [&str; 4] = [
+    "/usr/share/keymaps/include",
+    "/usr/share/kbd/keymaps/include",
+    "/usr/share/keymaps",
+    "/usr/share/kbd/keymaps"
+];
+
+// Key types, as defined in `linux/keyboard.h`
+const KT_LATIN: u16  = 0;
+const KT_FN: u16     = 1;
+const KT_SPEC: u16   = 2;
+const KT_PAD: u16    = 3;
+const KT_DEAD: u16   = 4;
+const KT_CONS: u16   = 5;
+const KT_CUR: u16    = 6;
+const KT_SHIFT: u16  = 7;
+const KT_META: u16   = 8;
+const KT_ASCII: u16  = 9;
+const KT_LOCK: u16   = 10;
+const KT_LETTER: u16 = 11;
+const KT_SLOCK: u16  = 12;
+
+const LATIN_SYMS: [&str; 256] = [
+    "nul", "Control_a", "Control_b", "Control_c", "Control_d", "Control_e", "Control_f", "Control_g",
+    "BackSpace", "Tab", "Linefeed", "Control_k", "Control_l", "Control_m", "Control_n", "Control_o",
+    "Control_p", "Control_q", "Control_r", "Control_s", "Control_t", "Control_u", "Control_v", "Control_w",
+    "Control_x", "Control_y", "Control_z", "Escape", "Control_backslash", "Control_bracketright", "Control_asciicircum", "Control_underscore",
+    "space", "exclam", "quotedbl", "numbersign", "dollar", "percent", "ampersand", "apostrophe",
+    "parenleft", "parenright", "asterisk", "plus", "comma", "minus", "period", "slash",
+    "zero", "one", "two", "three", "four", "five", "six", "seven",
+    "eight", "nine", "colon", "semicolon", "less", "equal", "greater", "question",
+    "at", "A", "B", "C", "D", "E", "F", "G",
+    "H", "I", "J", "K", "L", "M", "N", "O",
+    "P", "Q", "R", "S", "T", "U", "V", "W",
+    "X", "Y", "Z", "bracketleft", "backslash", "bracketright", "asciicircum", "underscore",
+    "grave", "a", "b", "c", "d", "e", "f", "g",
+    "h", "i", "j", "k", "l", "m", "n", "o",
+    "p", "q", "r", "s", "t", "u", "v", "w",
+    "x", "y", "z", "braceleft", "bar", "braceright", "asciitilde", "Delete",
+    "", "", "", "", "", "", "", "",
+    "", "", "", "", "", "", "", "",
+    "", "", "", "", "", "", "", "",
+    "", "", "", "", "", "", "", "",
+    "nobreakspace", "exclamdown", "cent", "sterling", "currency", "yen", "brokenbar", "section",
+    "diaeresis", "copyright", "ordfeminine", "guillemotleft", "notsign", "hyphen", "registered", "macron",
+    "degree", "plusminus", "twosuperior", "threesuperior", "acute", "mu", "paragraph", "periodcentered",
+    "cedilla", "onesuperior", "masculine", "guillemotright", "onequarter", "onehalf", "threequarters", "questiondown",
+    "Agrave", "Aacute", "Acircumflex", "Atilde", "Adiaeresis", "Aring", "AE", "Ccedilla",
+    "Egrave", "Eacute", "Ecircumflex", "Ediaeresis", "Igrave", "Iacute", "Icircumflex", "Idiaeresis",
+    "ETH", "Ntilde", "Ograve", "Oacute", "Ocircumflex", "Otilde", "Odiaeresis", "multiply",
+    "Ooblique", "Ugrave", "Uacute", "Ucircumflex", "Udiaeresis", "Yacute", "THORN", "ssharp",
+    "agrave", "aacute", "acircumflex", "atilde", "adiaeresis", "aring", "ae", "ccedilla",
+    "egrave", "eacute", "ecircumflex", "ediaeresis", "igrave", "iacute", "icircumflex", "idiaeresis",
+    "eth", "ntilde", "ograve", "oacute", "ocircumflex", "otilde", "odiaeresis", "division",
+    "oslash", "ugrave", "uacute", "ucircumflex", "udiaeresis", "yacute", "thorn", "ydiaeresis"
+];
+
+const FN_SYMS: [&str; 30] = [
+    "F1", "F2", "F3", "F4", "F5", "F6", "F7", "F8", "F9", "F10",
+    "F11", "F12", "F13", "F14", "F15", "F16", "F17", "F18", "F19", "F20",
+    "Find", "Insert", "Remove", "Select", "Prior", "Next", "Macro", "Help", "Do", "Pause"
+];
+
+const SPEC_SYMS: [&str; 20] = [
+    "VoidSymbol", "Return", "Show_Registers", "Show_Memory", "Show_State", "Send_Break", "Last_Console",
+    "Caps_Lock", "Num_Lock", "Scroll_Lock", "Scroll_Forward", "Scroll_Backward", "Boot", "Caps_On",
+    "Compose", "SAK", "Decr_Console", "Incr_Console", "KeyboardSignal", "Bare_Num_Lock"
+];
+
+const PAD_SYMS: [&str; 20] = [
+    "KP_0", "KP_1", "KP_2", "KP_3", "KP_4", "KP_5", "KP_6", "KP_7", "KP_8", "KP_9",
+    "KP_Add", "KP_Subtract", "KP_Multiply", "KP_Divide", "KP_Enter", "KP_Comma", "KP_Period", "KP_MinPlus",
+    "KP_Parenl", "KP_Parenr"
+];
+
+const DEAD_SYMS: [&str; 6] = [
+    "dead_grave", "dead_acute", "dead_circumflex", "dead_tilde", "dead_diaeresis", "dead_cedilla"
+];
+
+const CUR_SYMS: [&str; 4] = ["Down", "Left", "Right", "Up"];
+
+const SHIFT_SYMS: [&str; 9] = ["Shift", "AltGr", "Control", "Alt", "ShiftL", "ShiftR", "CtrlL", "CtrlR", "CapsShift"];
+
+const ASCII_SYMS: [&str; 26] = [
+    "Ascii_0", "Ascii_1", "Ascii_2", "Ascii_3", "Ascii_4", "Ascii_5", "Ascii_6", "Ascii_7", "Ascii_8", "Ascii_9",
+    "Hex_0", "Hex_1", "Hex_2", "Hex_3", "Hex_4", "Hex_5", "Hex_6", "Hex_7", "Hex_8", "Hex_9",
+    "Hex_A", "Hex_B", "Hex_C", "Hex_D", "Hex_E", "Hex_F"
+];
+
+const LOCK_SYMS: [&str; 8] = [
+    "Shift_Lock", "AltGr_Lock", "Control_Lock", "Alt_Lock", "ShiftL_Lock", "ShiftR_Lock", "CtrlL_Lock", "CtrlR_Lock"
+];
+
+const SLOCK_SYMS: [&str; 8] = ["SShift", "SAltGr", "SControl", "SAlt", "SShiftL", "SShiftR", "SCtrlL", "SCtrlR"];
+
+const SYNONYMS: [(&str, &str); 11] = [
+    ("quoteright", "apostrophe"),
+    ("quoteleft", "grave"),
+    ("Control_h", "BackSpace"),
+    ("Control_i", "Tab"),
+    ("Control_j", "Linefeed"),
+    ("Control_bracketleft", "Escape"),
+    ("Home", "Find"),
+    ("End", "Select"),
+    ("PageUp", "Prior"),
+    ("PageDown", "Next"),
+    ("Enter", "Return")
+];
+
+const MODIFIER_NAMES: [(&str, ShiftState); 9] = [
+    ("plain", ShiftState::empty()),
+    ("shift", ShiftState::SHIFT),
+    ("altgr", ShiftState::ALT_GR),
+    ("control", ShiftState::CTRL),
+    ("alt", ShiftState::ALT),
+    ("shiftl", ShiftState::SHIFT_LEFT),
+    ("shiftr", ShiftState::SHIFT_RIGHT),
+    ("ctrll", ShiftState::CTRL_LEFT),
+    ("ctrlr", ShiftState::CTRL_RIGHT)
+];
+
+impl Keymap {
+
+    /// Parses a keymap file in the format used by `loadkeys`, described in `keymaps(5)`.
+    /// 
+    /// Included files are searched in the directory of the including file and in its ancestors'
+    /// `include` subdirectories, and then in the standard keymap directories under `/usr/share`.
+    /// Compressed files are not supported. Keysyms can be given by name, as numbers, or as `U+XXXX`
+    /// Unicode code points. Function key strings defined with `string` and accent combinations defined
+    /// with `compose` are included in the keymap, while other statements like `charset` are ignored.
+    /// 
+    /// Errors in the file are reported with kind [`InvalidData`], together with the file name and line.
+    /// 
+    /// [`InvalidData`]: std::io::ErrorKind::InvalidData
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Keymap> {
+        let mut parser = Parser::new();
+        parser.parse_file(path.as_ref(), 0)?;
+        Ok(parser.keymap)
+    }
+
+    /// Parses a keymap in the format used by `loadkeys` from a string, like [`Keymap::from_file`] does.
+    /// Included files are searched only in the standard keymap directories.
+    /// 
+    /// [`Keymap::from_file`]: crate::Keymap::from_file
+    pub fn parse(source: &str) -> Result<Keymap> {
+        let mut parser = Parser::new();
+        parser.parse_source(source, Path::new("<string>"), None, 0)?;
+        Ok(parser.keymap)
+    }
+
+}
+
+impl<'a> Vt<'a> {
+
+    /// Loads a keymap file in the format used by `loadkeys` (see [`Keymap::from_file`]),
+    /// and applies it to the kernel like [`Vt::set_keymap`] does.
+    /// 
+    /// Returns `self` for chaining.
+    /// 
+    /// [`Keymap::from_file`]: crate::Keymap::from_file
+    /// [`Vt::set_keymap`]: crate::Vt::set_keymap
+    pub fn load_keymap_file<P: AsRef<Path>>(&mut self, path: P) -> Result<&mut Self> {
+        let keymap = Keymap::from_file(path)?;
+        self.set_keymap(&keymap)
+    }
+
+}
+
+/// A token of a keymap file.
+#[derive(Clone, Eq, PartialEq, Debug)]
+enum Token {
+    Word(String),
+    Str(Vec<u8>),
+    Char(char)
+}
+
+struct Parser {
+    keymap: Keymap,
+    /// Tables declared with the `keymaps` statement, filled in order by `keycode` lines
+    tables: Vec<ShiftState>
+}
+
+impl Parser {
+
+    fn new() -> Parser {
+        Parser {
+            keymap: Keymap::new(),
+            tables: Vec::new()
+        }
+    }
+
+    fn parse_file(&mut self, path: &Path, depth: usize) -> Result<()> {
+        let source = fs::read(path)?;
+        let source = String::from_utf8_lossy(&source);
+        self.parse_source(&source, path, path.parent(), depth)
+    }
+
+    fn parse_source(&mut self, source: &str, path: &Path, dir: Option<&Path>, depth: usize) -> Result<()> {
+        let mut lines = source.lines().enumerate();
+        while let Some((i, line)) = lines.next() {
+            // Lines ending with a backslash continue on the next one
+            let mut line = line.to_string();
+            while line.ends_with('\\') {
+                line.pop();
+                match lines.next() {
+                    Some((_, next)) => line.push_str(next),
+                    None => break
+                }
+            }
+
+            let error = |msg: String| VtError::new(io::ErrorKind::InvalidData, format!("{}:{}: {}", path.display(), i + 1, msg));
+            let tokens = tokenize(&line).map_err(error)?;
+            self.parse_statement(&tokens, dir, depth).map_err(|e| match e {
+                StatementError::Parse(msg) => error(msg),
+                StatementError::Other(e) => e
+            })?;
+        }
+        Ok(())
+    }
+
+    fn parse_statement(&mut self, tokens: &[Token], dir: Option<&Path>, depth: usize) -> std::result::Result<(), StatementError> {
+        let words: Vec<&str> = tokens.iter()
+            .map(|t| match t {
+                Token::Word(w) => w.as_str(),
+                Token::Str(_) => "\"",
+                Token::Char(_) => "'"
+            })
+            .collect();
+
+        match words.as_slice() {
+            [] => Ok(()),
+            ["keymaps", spec] => {
+                self.tables = parse_keymaps(spec)?;
+                Ok(())
+            },
+            ["include", "\""] => {
+                let name = match &tokens[1] {
+                    Token::Str(s) => String::from_utf8_lossy(s).into_owned(),
+                    _ => unreachable!()
+                };
+                if depth >= MAX_INCLUDE_DEPTH {
+                    return Err(StatementError::Parse(format!("Too many nested includes at {}", name)));
+                }
+                let path = find_include(&name, dir)
+                    .ok_or_else(|| StatementError::Parse(format!("Cannot find included file {}", name)))?;
+                self.parse_file(&path, depth + 1).map_err(StatementError::Other)
+            },
+            ["string", name, "=", "\""] => {
+                let sym = parse_keysym(name).filter(|s| s.value >> 8 == KT_FN)
+                    .ok_or_else(|| StatementError::Parse(format!("Not a function key: {}", name)))?;
+                if let Token::Str(string) = &tokens[3] {
+                    self.keymap.set_string(sym.value as u8, string);
+                }
+                Ok(())
+            },
+            ["compose", "as", "usual"] => Ok(()),
+            ["compose", _, _, "to", _] => {
+                let diacritic = Diacritic {
+                    accent: parse_char(&tokens[1])?,
+                    base: parse_char(&tokens[2])?,
+                    result: parse_char(&tokens[4])?
+                };
+                self.keymap.add_diacritic(diacritic);
+                Ok(())
+            },
+            ["charset", ..] | ["alt_is_meta"] | ["strings", "as", "usual"] => Ok(()),
+            _ => self.parse_keycode(&words)
+        }
+    }
+
+    /// Parses a line like `[modifiers] keycode N = keysyms...`.
+    fn parse_keycode(&mut self, words: &[&str]) -> std::result::Result<(), StatementError> {
+        let pos = words.iter().position(|w| *w == "keycode")
+            .ok_or_else(|| StatementError::Parse(format!("Unknown statement: {}", words[0])))?;
+
+        let mut modifiers = ShiftState::empty();
+        for name in &words[..pos] {
+            let m = MODIFIER_NAMES.iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, m)| *m)
+                .ok_or_else(|| StatementError::Parse(format!("Unknown modifier: {}", name)))?;
+            modifiers |= m;
+        }
+
+        let keycode = match &words[pos + 1..] {
+            [n, "=", ..] => parse_number(n)
+                .filter(|n| *n < 256)
+                .ok_or_else(|| StatementError::Parse(format!("Invalid keycode: {}", n)))? as u8,
+            _ => return Err(StatementError::Parse("Expected keycode N =".into()))
+        };
+        let syms = words[pos + 3..].iter()
+            .map(|s| parse_keysym(s).ok_or_else(|| StatementError::Parse(format!("Unknown keysym: {}", s))))
+            .collect::<std::result::Result<Vec<Keysym>, _>>()?;
+
+        if pos > 0 {
+            // Modifiers explicitly given: a single keysym for that table
+            match syms.as_slice() {
+                [sym] => {
+                    self.keymap.set(modifiers, keycode, sym.value);
+                    Ok(())
+                },
+                _ => Err(StatementError::Parse("Expected a single keysym after modifiers".into()))
+            }
+        } else if syms.len() == 1 {
+            // A single keysym applies to all the tables, following Shift, Ctrl and Alt for letters
+            let sym = syms[0];
+            for table in self.single_keysym_tables() {
+                let value = match sym.letter {
+                    Some(letter) => letter_value(letter, table),
+                    None => sym.value
+                };
+                self.keymap.set(table, keycode, value);
+            }
+            Ok(())
+        } else {
+            let tables = self.declared_tables();
+            if syms.len() > tables.len() {
+                return Err(StatementError::Parse(format!("Too many keysyms: only {} keymaps declared", tables.len())));
+            }
+            for (table, sym) in tables.into_iter().zip(syms) {
+                self.keymap.set(table, keycode, sym.value);
+            }
+            Ok(())
+        }
+    }
+
+    /// Returns the declared tables, or all of them in order if there is no `keymaps` statement.
+    fn declared_tables(&self) -> Vec<ShiftState> {
+        if self.tables.is_empty() {
+            (0..=255u8).map(ShiftState::from_bits_truncate).collect()
+        } else {
+            self.tables.clone()
+        }
+    }
+
+    /// Returns the tables filled by a `keycode` line with a single keysym: without a `keymaps` statement,
+    /// these are the tables used so far, so that new tables are not allocated needlessly.
+    fn single_keysym_tables(&self) -> Vec<ShiftState> {
+        if !self.tables.is_empty() {
+            self.tables.clone()
+        } else if self.keymap.tables().next().is_some() {
+            self.keymap.tables().collect()
+        } else {
+            vec![ShiftState::empty()]
+        }
+    }
+
+}
+
+enum StatementError {
+    Parse(String),
+    Other(VtError)
+}
+
+impl From<String> for StatementError {
+    fn from(msg: String) -> StatementError {
+        StatementError::Parse(msg)
+    }
+}
+
+/// Splits a line in words and quoted strings, stopping at comments.
+fn tokenize(line: &str) -> std::result::Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = line.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            '#' | '!' => break,
+            c if c.is_whitespace() => { chars.next(); },
+            '=' => {
+                chars.next();
+                tokens.push(Token::Word("=".into()));
+            },
+            '"' => {
+                chars.next();
+                tokens.push(Token::Str(parse_string(&mut chars)?));
+            },
+            '\'' => {
+                chars.next();
+                tokens.push(Token::Char(parse_char_literal(&mut chars)?));
+            },
+            _ => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || c == '=' || c == '"' || c == '#' {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                tokens.push(Token::Word(word));
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// Parses the contents of a quoted string, after the opening quote.
+fn parse_string(chars: &mut std::iter::Peekable<std::str::Chars>) -> std::result::Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    loop {
+        match chars.next() {
+            None => return Err("Unterminated string".into()),
+            Some('"') => return Ok(bytes),
+            Some('\\') => match chars.next() {
+                Some('n') => bytes.push(b'\n'),
+                Some(c @ '0'..='7') => {
+                    let mut value = c.to_digit(8).unwrap();
+                    for _ in 0..2 {
+                        match chars.peek().and_then(|c| c.to_digit(8)) {
+                            Some(d) => {
+                                value = value * 8 + d;
+                                chars.next();
+                            },
+                            None => break
+                        }
+                    }
+                    bytes.push(value as u8);
+                },
+                Some(c) => {
+                    let mut buf = [0u8; 4];
+                    bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                },
+                None => return Err("Unterminated string".into())
+            },
+            Some(c) => {
+                let mut buf = [0u8; 4];
+                bytes.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            }
+        }
+    }
+}
+
+/// Parses the contents of a character literal, after the opening quote.
+fn parse_char_literal(chars: &mut std::iter::Peekable<std::str::Chars>) -> std::result::Result<char, String> {
+    let c = match chars.next() {
+        Some('\\') => match chars.next() {
+            Some(c @ '0'..='7') => {
+                let mut value = c.to_digit(8).unwrap();
+                while let Some(d) = chars.peek().and_then(|c| c.to_digit(8)) {
+                    value = value * 8 + d;
+                    chars.next();
+                }
+                std::char::from_u32(value).ok_or("Invalid character literal")?
+            },
+            Some(c) => c,
+            None => return Err("Unterminated character literal".into())
+        },
+        Some(c) => c,
+        None => return Err("Unterminated character literal".into())
+    };
+    match chars.next() {
+        Some('\'') => Ok(c),
+        _ => Err("Unterminated character literal".into())
+    }
+}
+
+/// Returns the character for a `compose` operand: a character literal, or a keysym producing a character.
+fn parse_char(token: &Token) -> std::result::Result<char, String> {
+    match token {
+        Token::Char(c) => Ok(*c),
+        Token::Word(w) => {
+            parse_keysym(w)
+                .and_then(|sym| match sym.value >> 8 {
+                    KT_LATIN | KT_LETTER => Some((sym.value & 0xff) as u8 as char),
+                    t if t >= 0xf0 => std::char::from_u32((sym.value ^ 0xf000) as u32),
+                    _ => None
+                })
+                .ok_or_else(|| format!("Not a character: {}", w))
+        },
+        Token::Str(_) => Err("Unexpected string".into())
+    }
+}
+
+/// Parses a list of table numbers and ranges, like `0-2,4,8-9`.
+fn parse_keymaps(spec: &str) -> std::result::Result<Vec<ShiftState>, String> {
+    let mut tables = Vec::new();
+    for part in spec.split(',') {
+        let mut bounds = part.splitn(2, '-').map(parse_number);
+        let (start, end) = match (bounds.next().flatten(), bounds.next()) {
+            (Some(start), None) => (start, start),
+            (Some(start), Some(Some(end))) if end >= start => (start, end),
+            _ => return Err(format!("Invalid keymaps: {}", spec))
+        };
+        if end > 255 {
+            return Err(format!("Keymap out of range: {}", end));
+        }
+        tables.extend((start..=end).map(|t| ShiftState::from_bits_truncate(t as u8)));
+    }
+    Ok(tables)
+}
+
+/// Parses a decimal, hexadecimal (`0x`) or octal (leading `0`) number.
+fn parse_number(s: &str) -> Option<u32> {
+    if let Some(hex) = s.strip_prefix("0x") {
+        u32::from_str_radix(hex, 16).ok()
+    } else if s.len() > 1 && s.starts_with('0') {
+        u32::from_str_radix(&s[1..], 8).ok()
+    } else {
+        s.parse().ok()
+    }
+}
+
+fn find_include(name: &str, dir: Option<&Path>) -> Option<PathBuf> {
+    let mut dirs: Vec<PathBuf> = Vec::new();
+    if let Some(dir) = dir {
+        dirs.push(dir.to_path_buf());
+        dirs.extend(dir.ancestors().map(|d| d.join("include")));
+    }
+    dirs.extend(INCLUDE_DIRS.iter().map(PathBuf::from));
+
+    let candidates = [name.to_string(), format!("{}.inc", name), format!("{}.map", name)];
+    dirs.iter()
+        .flat_map(|d| candidates.iter().map(move |c| d.join(c)))
+        .find(|p| p.is_file())
+}
+
+#[derive(Copy, Clone)]
+struct Keysym {
+    value: u16,
+    /// Lowercase Latin-1 code of the keysym, if it is a letter affected by CapsLock
+    letter: Option<u8>
+}
+
+fn parse_keysym(s: &str) -> Option<Keysym> {
+    // A leading `+` explicitly marks a letter
+    if let Some(name) = s.strip_prefix('+') {
+        let sym = parse_keysym(name)?;
+        return Some(Keysym { value: (KT_LETTER << 8) | (sym.value & 0xff), letter: None });
+    }
+
+    if let Some(hex) = s.strip_prefix("U+") {
+        let code = u16::from_str_radix(hex, 16).ok()?;
+        return Some(Keysym { value: code ^ 0xf000, letter: None });
+    }
+
+    if let Some(n) = parse_number(s) {
+        return u16::try_from(n).ok().map(|value| Keysym { value, letter: None });
+    }
+
+    let name = SYNONYMS.iter().find(|(syn, _)| *syn == s).map_or(s, |(_, name)| *name);
+
+    if let Some(code) = LATIN_SYMS.iter().position(|n| !n.is_empty() && *n == name) {
+        let code = code as u8;
+        return Some(Keysym { value: (KT_LATIN << 8) | code as u16, letter: lowercase_letter(code) });
+    }
+
+    if let Some(meta) = name.strip_prefix("Meta_") {
+        let sym = parse_keysym(meta)?;
+        if sym.value >> 8 != KT_LATIN {
+            return None;
+        }
+        return Some(Keysym { value: (KT_META << 8) | sym.value, letter: None });
+    }
+
+    if let Some(n) = name.strip_prefix("Console_").and_then(|n| n.parse::<u16>().ok()) {
+        return if (1..=63).contains(&n) {
+            Some(Keysym { value: (KT_CONS << 8) | (n - 1), letter: None })
+        } else {
+            None
+        };
+    }
+
+    if let Some(n) = name.strip_prefix('F').and_then(|n| n.parse::<u16>().ok()) {
+        // F21 and above follow the editing keys
+        return match n {
+            1..=20 => Some(Keysym { value: (KT_FN << 8) | (n - 1), letter: None }),
+            21..=246 => Some(Keysym { value: (KT_FN << 8) | (n + 9), letter: None }),
+            _ => None
+        };
+    }
+
+    let tables: [(u16, &[&str]); 9] = [
+        (KT_FN, &FN_SYMS), (KT_SPEC, &SPEC_SYMS), (KT_PAD, &PAD_SYMS), (KT_DEAD, &DEAD_SYMS),
+        (KT_CUR, &CUR_SYMS), (KT_SHIFT, &SHIFT_SYMS), (KT_ASCII, &ASCII_SYMS), (KT_LOCK, &LOCK_SYMS),
+        (KT_SLOCK, &SLOCK_SYMS)
+    ];
+    tables.iter()
+        .find_map(|(kt, syms)| syms.iter().position(|n| *n == name).map(|i| (kt << 8) | i as u16))
+        .map(|value| Keysym { value, letter: None })
+}
+
+/// Returns the lowercase version of the given Latin-1 character, if it is a letter with an uppercase version.
+fn lowercase_letter(code: u8) -> Option<u8> {
+    match code {
+        b'a'..=b'z' | 0xe0..=0xfe if code != 0xf7 => Some(code),
+        b'A'..=b'Z' | 0xc0..=0xde if code != 0xd7 => Some(code + 0x20),
+        _ => None
+    }
+}
+
+/// Computes the value of a letter in the given table, the way `loadkeys` expands
+/// a `keycode` line with a single letter.
+fn letter_value(letter: u8, table: ShiftState) -> u16 {
+    let shift = table.intersects(ShiftState::SHIFT | ShiftState::SHIFT_LEFT | ShiftState::SHIFT_RIGHT);
+    let ctrl = table.intersects(ShiftState::CTRL | ShiftState::CTRL_LEFT | ShiftState::CTRL_RIGHT);
+    let alt = table.contains(ShiftState::ALT);
+
+    // Only ASCII letters have a control character
+    let ctrl = ctrl && letter.is_ascii_lowercase();
+    let code = if shift { letter - 0x20 } else { letter };
+    match (ctrl, alt) {
+        (false, false) => (KT_LETTER << 8) | code as u16,
+        (true, false) => (KT_LATIN << 8) | (code & 0x1f) as u16,
+        (false, true) => (KT_META << 8) | code as u16,
+        (true, true) => (KT_META << 8) | (code & 0x1f) as u16
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(bits: u8) -> ShiftState {
+        ShiftState::from_bits_truncate(bits)
+    }
+
+    fn value(line: &str) -> u16 {
+        let keymap = Keymap::parse(&format!("keymaps 0\nkeycode 1 = {}", line)).unwrap();
+        keymap.get(table(0), 1).unwrap()
+    }
+
+    #[test]
+    fn parses_keysyms() {
+        assert_eq!(value("a"), 0x0b61);
+        assert_eq!(value("Escape"), 0x001b);
+        assert_eq!(value("Control_bracketleft"), 0x001b);
+        assert_eq!(value("+eacute"), 0x0be9);
+        assert_eq!(value("nobreakspace"), 0x00a0);
+        assert_eq!(value("Return"), 0x0201);
+        assert_eq!(value("Up"), 0x0603);
+        assert_eq!(value("PageUp"), 0x0118);
+        assert_eq!(value("Caps_Lock"), 0x0207);
+        assert_eq!(value("U+20AC"), 0xd0ac);
+        assert_eq!(value("0x0b41"), 0x0b41);
+        assert_eq!(value("0101"), 0x0041);
+        assert_eq!(value("1234"), 1234);
+    }
+
+    #[test]
+    fn parses_numbered_keysyms() {
+        assert_eq!(value("F1"), 0x0100);
+        assert_eq!(value("F20"), 0x0113);
+        assert_eq!(value("F21"), 0x011e);
+        assert_eq!(value("F246"), 0x01ff);
+        assert_eq!(value("Meta_a"), 0x0861);
+        assert_eq!(value("Meta_Escape"), 0x081b);
+        assert_eq!(value("Console_1"), 0x0500);
+        assert_eq!(value("Console_63"), 0x053e);
+
+        for invalid in &["F0", "F247", "Console_0", "Console_64", "Meta_F1", "U+GGGG", "0x10000", "NoSuchKey"] {
+            assert!(Keymap::parse(&format!("keycode 1 = {}", invalid)).is_err(), "{} should be rejected", invalid);
+        }
+    }
+
+    #[test]
+    fn parses_keymaps_ranges() {
+        let keymap = Keymap::parse("keymaps 0-2,4,8-9\nkeycode 2 = one exclam at two three six").unwrap();
+        let tables: Vec<u8> = keymap.tables().map(|t| t.bits()).collect();
+        assert_eq!(tables, vec![0, 1, 2, 4, 8, 9]);
+        assert_eq!(keymap.get(table(1), 2), Some(0x0021));
+        assert_eq!(keymap.get(table(9), 2), Some(value("six")));
+
+        assert!(Keymap::parse("keymaps 0-256").is_err());
+        assert!(Keymap::parse("keymaps 2-1").is_err());
+        assert!(Keymap::parse("keymaps 0-1\nkeycode 2 = one two three").is_err());
+    }
+
+    #[test]
+    fn expands_single_letters() {
+        let keymap = Keymap::parse("keymaps 0-2,4,8,12\nkeycode 30 = a\nkeycode 40 = eacute").unwrap();
+        assert_eq!(keymap.get(table(0), 30), Some(0x0b61));
+        assert_eq!(keymap.get(table(1), 30), Some(0x0b41));
+        assert_eq!(keymap.get(table(2), 30), Some(0x0b61));
+        assert_eq!(keymap.get(table(4), 30), Some(0x0001));
+        assert_eq!(keymap.get(table(8), 30), Some(0x0861));
+        assert_eq!(keymap.get(table(12), 30), Some(0x0801));
+
+        // Only ASCII letters have a control character
+        assert_eq!(keymap.get(table(1), 40), Some(0x0bc9));
+        assert_eq!(keymap.get(table(4), 40), Some(0x0be9));
+    }
+
+    #[test]
+    fn parses_modifier_lines() {
+        let keymap = Keymap::parse("keymaps 0-15\nkeycode 2 = one\nshift alt keycode 2 = Meta_exclam\nplain keycode 3 = two").unwrap();
+        assert_eq!(keymap.get(table(0), 2), Some(0x0031));
+        assert_eq!(keymap.get(table(9), 2), Some(0x0821));
+        assert_eq!(keymap.get(table(0), 3), Some(0x0032));
+        assert!(Keymap::parse("hyper keycode 2 = one").is_err());
+        assert!(Keymap::parse("shift keycode 2 = one two").is_err());
+    }
+
+    #[test]
+    fn parses_strings_and_compose() {
+        let source = r#"
+            string F1 = "\033[[A"
+            string F21 = "a\"b\\c\n"
+            compose '`' 'a' to agrave
+            compose '\047' e to 'é'
+        "#;
+        let keymap = Keymap::parse(source).unwrap();
+        assert_eq!(keymap.string(0), Some(&b"\x1b[[A"[..]));
+        assert_eq!(keymap.string(30), Some(&b"a\"b\\c\n"[..]));
+        assert_eq!(keymap.diacritics(), &[
+            Diacritic { accent: '`', base: 'a', result: 'à' },
+            Diacritic { accent: '\'', base: 'e', result: 'é' }
+        ]);
+
+        assert!(Keymap::parse("string Up = \"x\"").is_err());
+        assert!(Keymap::parse("string F1 = \"x").is_err());
+    }
+
+    #[test]
+    fn skips_comments_and_joins_lines() {
+        let source = "# comment\n! another\nkeymaps 0-1 # trailing\nkeycode 2 = \\\n one exclam\ncharset \"iso-8859-1\"\n";
+        let keymap = Keymap::parse(source).unwrap();
+        assert_eq!(keymap.get(table(1), 2), Some(0x0021));
+    }
+
+    #[test]
+    fn reports_line_numbers() {
+        let err = Keymap::parse("keymaps 0\n\nkeycode 2 = NoSuchKey").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("<string>:3:"), "{}", err);
+    }
+
+}
@@ -21,12 +21,51 @@
 //! ```
 //! 
 //! For a more complete example, see the files in the `examples` folder.
+//! 
+//! ## Optional features
+//! 
+//! - `tokio`: enables `AsyncVt`, an asynchronous wrapper around [`Vt`] for the Tokio runtime.
+//! - `mio`: implements `mio::event::Source` for [`Vt`], to register it in a `mio` event loop.
+//! - `tracing-subscriber`: implements `tracing_subscriber::fmt::MakeWriter` for [`VtWriter`], to send traces to a terminal.
+//! - `log`: implements `log::Log` for [`VtWriter`], to send log records to a terminal.
 
 #[macro_use] extern crate bitflags;
 
 mod ffi;
+mod units;
+mod error;
 mod console;
 mod vt;
+mod recovery;
+mod screen;
+mod font;
+mod takeover;
+mod watcher;
+mod session;
+mod input;
+mod keymap;
+mod keymap_file;
+mod line_editor;
+mod writer;
+mod style;
+mod buffered;
+#[cfg(feature = "tokio")]
+mod async_vt;
 
+pub use crate::error::*;
 pub use crate::console::*;
-pub use crate::vt::*;
\ No newline at end of file
+pub use crate::vt::*;
+pub use crate::recovery::*;
+pub use crate::screen::*;
+pub use crate::font::*;
+pub use crate::takeover::*;
+pub use crate::watcher::*;
+pub use crate::session::*;
+pub use crate::input::*;
+pub use crate::keymap::*;
+pub use crate::line_editor::*;
+pub use crate::writer::*;
+pub use crate::style::*;
+pub use crate::buffered::*;
+#[cfg(feature = "tokio")]
+pub use crate::async_vt::*;
\ No newline at end of file
@@ -0,0 +1,256 @@
+use std::io::{self, Write};
+use crate::error::{Result, VtError};
+use crate::input::{Key, KeyEvent, KeyModifiers};
+use crate::vt::Vt;
+
+const DEFAULT_HISTORY_LIMIT: usize = 100;
+
+type Completer = Box<dyn FnMut(&str) -> Vec<String>>;
+
+/// A minimal line editor reading from a [`Vt`], for interactive tools which do not need a full readline.
+/// 
+/// While a line is being edited, the following keys are supported:
+/// 
+/// - `Left`/`Right`, `Home`/`End` (or `Ctrl+A`/`Ctrl+E`) move the cursor;
+/// - `Backspace` and `Delete` remove the character before or under the cursor;
+/// - `Ctrl+U` and `Ctrl+K` remove everything before or after the cursor;
+/// - `Up`/`Down` browse the history of the previously entered lines;
+/// - `Tab` invokes the completion hook set with [`LineEditor::set_completer`];
+/// - `Enter` accepts the line, `Ctrl+D` on an empty line signals end of input and `Ctrl+C` aborts.
+/// 
+/// Signal generation is disabled while a line is being read, so `Ctrl+C` does not send `SIGINT`.
+/// 
+/// The line is redrawn in place on every change, so it should fit in the width of the terminal.
+/// 
+/// [`Vt`]: crate::Vt
+/// [`LineEditor::set_completer`]: crate::LineEditor::set_completer
+pub struct LineEditor {
+    history: Vec<String>,
+    history_limit: usize,
+    completer: Option<Completer>
+}
+
+impl LineEditor {
+
+    /// Creates a new line editor with an empty history, remembering up to 100 lines.
+    pub fn new() -> LineEditor {
+        LineEditor {
+            history: Vec::new(),
+            history_limit: DEFAULT_HISTORY_LIMIT,
+            completer: None
+        }
+    }
+
+    /// Sets the maximum number of lines kept in the history, discarding the oldest ones if needed.
+    /// 
+    /// Returns `self` for chaining.
+    pub fn set_history_limit(&mut self, limit: usize) -> &mut Self {
+        self.history_limit = limit;
+        self.trim_history();
+        self
+    }
+
+    /// Returns the lines in the history, from the oldest to the newest.
+    pub fn history(&self) -> &[String] {
+        &self.history
+    }
+
+    /// Adds a line to the history. Empty lines and lines equal to the last one are ignored.
+    /// 
+    /// Returns `self` for chaining.
+    pub fn add_history(&mut self, line: &str) -> &mut Self {
+        if !line.is_empty() && self.history.last().map(String::as_str) != Some(line) {
+            self.history.push(line.to_string());
+            self.trim_history();
+        }
+        self
+    }
+
+    /// Sets the hook invoked when `Tab` is pressed.
+    /// 
+    /// The hook receives the text before the cursor, and returns the candidates to replace it with.
+    /// A single candidate is inserted as is, while multiple candidates are reduced to their longest common prefix.
+    /// 
+    /// Returns `self` for chaining.
+    pub fn set_completer<F>(&mut self, completer: F) -> &mut Self
+        where F: FnMut(&str) -> Vec<String> + 'static
+    {
+        self.completer = Some(Box::new(completer));
+        self
+    }
+
+    /// Shows `prompt` and reads a line from the given terminal, returning it without the trailing newline.
+    /// 
+    /// Returns `None` if `Ctrl+D` is pressed on an empty line, and an error of kind [`Interrupted`]
+    /// if `Ctrl+C` is pressed. Accepted non-empty lines are added to the history.
+    /// 
+    /// [`Interrupted`]: std::io::ErrorKind::Interrupted
+    pub fn read_line(&mut self, vt: &mut Vt, prompt: &str) -> Result<Option<String>> {
+        vt.without_signals(|vt| self.edit_line(vt, prompt))
+    }
+
+    fn edit_line(&mut self, vt: &mut Vt, prompt: &str) -> Result<Option<String>> {
+        let mut state = EditState {
+            prompt,
+            line: Vec::new(),
+            cursor: 0,
+            history_index: self.history.len(),
+            saved_line: Vec::new()
+        };
+        state.redraw(vt)?;
+
+        loop {
+            let KeyEvent { key, modifiers } = vt.read_key_event()?;
+            let ctrl = modifiers.contains(KeyModifiers::CTRL);
+            match key {
+                Key::Enter => {
+                    vt.write_all(b"\r\n")?;
+                    let line: String = state.line.iter().collect();
+                    self.add_history(&line);
+                    return Ok(Some(line));
+                },
+                Key::Char('c') if ctrl => {
+                    vt.write_all(b"^C\r\n")?;
+                    return Err(VtError::new(io::ErrorKind::Interrupted, "Line editing interrupted"));
+                },
+                Key::Char('d') if ctrl => {
+                    if state.line.is_empty() {
+                        vt.write_all(b"\r\n")?;
+                        return Ok(None);
+                    }
+                    if state.cursor < state.line.len() {
+                        state.line.remove(state.cursor);
+                    }
+                },
+                Key::Char('a') if ctrl => state.cursor = 0,
+                Key::Char('e') if ctrl => state.cursor = state.line.len(),
+                Key::Char('u') if ctrl => {
+                    state.line.drain(..state.cursor);
+                    state.cursor = 0;
+                },
+                Key::Char('k') if ctrl => state.line.truncate(state.cursor),
+                Key::Char(_) if ctrl || modifiers.contains(KeyModifiers::ALT) => continue,
+                Key::Char(c) => {
+                    state.line.insert(state.cursor, c);
+                    state.cursor += 1;
+                },
+                Key::Backspace => {
+                    if state.cursor > 0 {
+                        state.cursor -= 1;
+                        state.line.remove(state.cursor);
+                    }
+                },
+                Key::Delete => {
+                    if state.cursor < state.line.len() {
+                        state.line.remove(state.cursor);
+                    }
+                },
+                Key::Left => state.cursor = state.cursor.saturating_sub(1),
+                Key::Right => state.cursor = (state.cursor + 1).min(state.line.len()),
+                Key::Home => state.cursor = 0,
+                Key::End => state.cursor = state.line.len(),
+                Key::Up => {
+                    if state.history_index > 0 {
+                        if state.history_index == self.history.len() {
+                            state.saved_line = state.line.clone();
+                        }
+                        state.history_index -= 1;
+                        state.set_line(self.history[state.history_index].chars().collect());
+                    }
+                },
+                Key::Down => {
+                    if state.history_index < self.history.len() {
+                        state.history_index += 1;
+                        let line = match self.history.get(state.history_index) {
+                            Some(line) => line.chars().collect(),
+                            None => std::mem::take(&mut state.saved_line)
+                        };
+                        state.set_line(line);
+                    }
+                },
+                Key::Tab => {
+                    if !self.complete(&mut state) {
+                        vt.write_all(b"\x07")?;
+                    }
+                },
+                _ => continue
+            }
+            state.redraw(vt)?;
+        }
+    }
+
+    /// Runs the completion hook, returning `false` if the line has not changed.
+    fn complete(&mut self, state: &mut EditState) -> bool {
+        let completer = match self.completer {
+            Some(ref mut completer) => completer,
+            None => return false
+        };
+
+        let before: String = state.line[..state.cursor].iter().collect();
+        let candidates = completer(&before);
+        let replacement: Vec<char> = match candidates.split_first() {
+            None => return false,
+            Some((first, rest)) => {
+                let mut prefix: Vec<char> = first.chars().collect();
+                for candidate in rest {
+                    let common = prefix.iter().zip(candidate.chars()).take_while(|(a, b)| **a == *b).count();
+                    prefix.truncate(common);
+                }
+                prefix
+            }
+        };
+        if replacement.iter().cloned().eq(before.chars()) {
+            return false;
+        }
+
+        let len = replacement.len();
+        state.line.splice(..state.cursor, replacement);
+        state.cursor = len;
+        true
+    }
+
+    fn trim_history(&mut self) {
+        if self.history.len() > self.history_limit {
+            let excess = self.history.len() - self.history_limit;
+            self.history.drain(..excess);
+        }
+    }
+
+}
+
+impl Default for LineEditor {
+    fn default() -> LineEditor {
+        LineEditor::new()
+    }
+}
+
+/// State of the line being edited.
+struct EditState<'p> {
+    prompt: &'p str,
+    line: Vec<char>,
+    cursor: usize,
+    history_index: usize,
+    /// Line being edited before browsing the history
+    saved_line: Vec<char>
+}
+
+impl<'p> EditState<'p> {
+
+    fn set_line(&mut self, line: Vec<char>) {
+        self.cursor = line.len();
+        self.line = line;
+    }
+
+    /// Redraws the prompt and the line, and moves the cursor to its position.
+    fn redraw(&self, vt: &mut Vt) -> Result<()> {
+        let line: String = self.line.iter().collect();
+        let mut out = format!("\r{}{}\x1b[K", self.prompt, line);
+        let back = self.line.len() - self.cursor;
+        if back > 0 {
+            out.push_str(&format!("\x1b[{}D", back));
+        }
+        vt.write_all(out.as_bytes())?;
+        Ok(())
+    }
+
+}
@@ -0,0 +1,69 @@
+use std::io::Write;
+use std::fs::{File, OpenOptions};
+use std::panic;
+use std::os::unix::io::AsRawFd;
+use nix::sys::termios::{InputFlags, OutputFlags, LocalFlags, SetArg, tcgetattr, tcsetattr};
+use crate::ffi;
+use crate::error::{Result, VtError};
+use crate::console::Console;
+
+/// Installs a panic hook that brings the active virtual terminal back to a usable text state.
+/// 
+/// When a panic occurs, the hook restores the foreground terminal to text mode (`KD_TEXT`),
+/// sets its keyboard back to translated mode (`K_XLATE`), re-enables canonical input, echo and signals,
+/// and makes the cursor visible again. After that, the previously installed hook is invoked,
+/// so the default panic message (or any custom hook) still works as before.
+/// 
+/// This is meant as a last resort safety net for programs that put the console in graphics or raw mode:
+/// the hook runs both when panics unwind and when they abort, but it is not run at all
+/// if the process is terminated by a signal or exits without panicking.
+/// Errors during the restoration are ignored, since there is nothing sensible left to do.
+pub fn install_panic_restore(console: Console) {
+    let previous_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let _ = restore_active_vt(&console);
+        previous_hook(info);
+    }));
+}
+
+impl Console {
+
+    /// Forces the keyboard of the currently active terminal back to translated mode (`K_XLATE`),
+    /// like `kbd_mode -a` does.
+    /// 
+    /// If a program crashes while the keyboard is in raw mode or disabled, the console becomes unusable:
+    /// any program changing the keyboard mode should call this from its panic and signal cleanup paths.
+    /// See also [`install_panic_restore`].
+    /// 
+    /// [`install_panic_restore`]: crate::install_panic_restore
+    pub fn reset_active_keyboard(&self) -> Result<()> {
+        let file = open_active_vt(self)?;
+        ffi::kdskbmode(file.as_raw_fd(), ffi::K_XLATE)
+    }
+
+}
+
+fn open_active_vt(console: &Console) -> Result<File> {
+    let n = console.current_vt_number()?;
+    OpenOptions::new().read(true).write(true).open(format!("/dev/tty{}", n))
+        .map_err(VtError::from)
+}
+
+fn restore_active_vt(console: &Console) -> Result<()> {
+    let mut file = open_active_vt(console)?;
+    let fd = file.as_raw_fd();
+
+    // Restore the console modes first, so that the cursor and the panic message are visible
+    let _ = ffi::kdsetmode(fd, ffi::KD_TEXT);
+    let _ = ffi::kdskbmode(fd, ffi::K_XLATE);
+
+    if let Ok(mut termios) = tcgetattr(fd) {
+        termios.input_flags |= InputFlags::ICRNL | InputFlags::IXON;
+        termios.output_flags |= OutputFlags::OPOST | OutputFlags::ONLCR;
+        termios.local_flags |= LocalFlags::ICANON | LocalFlags::ECHO | LocalFlags::ECHOE | LocalFlags::ISIG | LocalFlags::IEXTEN;
+        let _ = tcsetattr(fd, SetArg::TCSANOW, &termios);
+    }
+
+    write!(file, "\x1b[?25h")?;
+    Ok(())
+}
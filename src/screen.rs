@@ -0,0 +1,471 @@
+use std::io::{self, Read, Write, Seek, SeekFrom};
+use std::fs::{File, OpenOptions};
+use std::ops::{Deref, DerefMut};
+use std::os::unix::io::AsRawFd;
+use nix::libc::c_int;
+use crate::ffi;
+use crate::error::{Result, VtError};
+use crate::vt::Vt;
+
+/// Attribute of a cell of the screen, in the VGA format used by the kernel:
+/// the lower 4 bits contain the foreground color (the highest of which is the intensity bit),
+/// the next 3 bits the background color and the highest bit the blink flag.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct CellAttribute(u8);
+
+impl CellAttribute {
+
+    /// Creates a new attribute with the given foreground (`0-15`) and background (`0-7`) colors.
+    /// Out of range colors are truncated.
+    pub fn new(foreground: u8, background: u8) -> CellAttribute {
+        CellAttribute((foreground & 0x0F) | ((background & 0x07) << 4))
+    }
+
+    /// Creates a new attribute from its raw VGA representation.
+    pub fn from_byte(byte: u8) -> CellAttribute {
+        CellAttribute(byte)
+    }
+
+    /// Returns the raw VGA representation of this attribute.
+    pub fn as_byte(self) -> u8 {
+        self.0
+    }
+
+    /// Returns the foreground color (`0-15`).
+    pub fn foreground(self) -> u8 {
+        self.0 & 0x0F
+    }
+
+    /// Returns the background color (`0-7`).
+    pub fn background(self) -> u8 {
+        (self.0 >> 4) & 0x07
+    }
+
+    /// Returns whether the cell is blinking.
+    pub fn is_blinking(self) -> bool {
+        self.0 & 0x80 != 0
+    }
+
+    /// Returns a copy of this attribute with the blink flag set to the given value.
+    pub fn with_blink(self, blink: bool) -> CellAttribute {
+        if blink {
+            CellAttribute(self.0 | 0x80)
+        } else {
+            CellAttribute(self.0 & !0x80)
+        }
+    }
+
+}
+
+impl Default for CellAttribute {
+    /// The default attribute of the console: light gray on black.
+    fn default() -> CellAttribute {
+        CellAttribute::new(7, 0)
+    }
+}
+
+/// A cell of the screen, as stored by the kernel.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct Cell {
+    /// Index of the glyph displayed in the cell, in the current font.
+    pub glyph: u8,
+    /// Colors and blink flag of the cell.
+    pub attribute: CellAttribute
+}
+
+impl Cell {
+
+    /// Returns the character displayed in the cell, converting the glyph index as Latin-1.
+    /// This is exact for ASCII text.
+    pub fn char(self) -> char {
+        self.glyph as char
+    }
+
+}
+
+/// Contents of the screen of a virtual terminal at a given moment, as returned by [`Vt::snapshot`].
+/// 
+/// [`Vt::snapshot`]: crate::Vt::snapshot
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct ScreenSnapshot {
+    rows: u16,
+    cols: u16,
+    cursor: (u16, u16),
+    cells: Vec<Cell>
+}
+
+impl ScreenSnapshot {
+
+    /// Returns the number of rows of the screen.
+    pub fn rows(&self) -> u16 {
+        self.rows
+    }
+
+    /// Returns the number of columns of the screen.
+    pub fn cols(&self) -> u16 {
+        self.cols
+    }
+
+    /// Returns the position of the cursor as `(row, col)`, like [`ScreenSnapshot::cell`] takes it.
+    /// Coordinates are 0-based.
+    /// 
+    /// [`ScreenSnapshot::cell`]: crate::ScreenSnapshot::cell
+    pub fn cursor_position(&self) -> (u16, u16) {
+        self.cursor
+    }
+
+    /// Returns all the cells of the screen, row by row.
+    pub fn cells(&self) -> &[Cell] {
+        &self.cells
+    }
+
+    /// Returns the cells of the given row, or `None` if the row is outside of the screen.
+    /// Rows are 0-based.
+    pub fn row(&self, row: u16) -> Option<&[Cell]> {
+        if row >= self.rows {
+            return None;
+        }
+        let start = row as usize * self.cols as usize;
+        Some(&self.cells[start..start + self.cols as usize])
+    }
+
+    /// Returns the cell at the given position, or `None` if the position is outside of the screen.
+    /// Coordinates are 0-based.
+    pub fn cell(&self, row: u16, col: u16) -> Option<Cell> {
+        if col >= self.cols {
+            return None;
+        }
+        self.row(row).map(|cells| cells[col as usize])
+    }
+
+    /// Returns the text of the screen, one string per row, without trailing spaces.
+    pub fn text(&self) -> Vec<String> {
+        (0..self.rows)
+            .filter_map(|row| self.row(row))
+            .map(|cells| cells.iter().map(|c| c.char()).collect::<String>().trim_end_matches(' ').to_string())
+            .collect()
+    }
+
+}
+
+/// How text is selected by [`Vt::set_selection`].
+/// 
+/// [`Vt::set_selection`]: crate::Vt::set_selection
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum SelectionMode {
+    /// Select the characters between the start and the end position.
+    Char,
+    /// Select whole words, extending the selection to the word boundaries.
+    Word,
+    /// Select whole lines.
+    Line,
+    /// Do not select anything, just show the mouse pointer at the end position.
+    Pointer,
+    /// Remove the current selection.
+    Clear
+}
+
+/// Header of the `/dev/vcsa<N>` devices.
+pub(crate) struct VcsaHeader {
+    pub rows: u8,
+    pub cols: u8,
+    pub cursor_x: u8,
+    pub cursor_y: u8
+}
+
+pub(crate) fn open_vcsa(vt: &Vt, write: bool) -> Result<File> {
+    open_screen_device(vt, "vcsa", write)
+}
+
+/// Opens one of the screen devices of `vt`, `/dev/vcs<N>` or `/dev/vcsa<N>`.
+fn open_screen_device(vt: &Vt, device: &str, write: bool) -> Result<File> {
+    let path = format!("/dev/{}{}", device, vt.number());
+    OpenOptions::new().read(true).write(write).open(&path).map_err(|e| match e.kind() {
+        io::ErrorKind::PermissionDenied => VtError::PermissionDenied { hint: "accessing the /dev/vcs devices usually requires root privileges" },
+        _ => e.into()
+    })
+}
+
+pub(crate) fn read_vcsa_header(file: &mut File) -> Result<VcsaHeader> {
+    let mut header = [0u8; 4];
+    file.seek(SeekFrom::Start(0))?;
+    file.read_exact(&mut header)?;
+    Ok(VcsaHeader {
+        rows: header[0],
+        cols: header[1],
+        cursor_x: header[2],
+        cursor_y: header[3]
+    })
+}
+
+impl<'a> Vt<'a> {
+
+    /// Returns the text currently displayed on the screen, one string per row,
+    /// reading it from `/dev/vcs<N>`. Trailing spaces are removed from each row.
+    /// 
+    /// The kernel stores one byte per cell, which is the index of the glyph in the current font:
+    /// bytes are converted as Latin-1, which is exact for ASCII text.
+    /// Use [`Vt::snapshot`] to get the attributes of the cells too.
+    /// 
+    /// [`Vt::snapshot`]: crate::Vt::snapshot
+    pub fn capture_text(&self) -> Result<Vec<String>> {
+        let cols = self.columns()? as usize;
+        let mut bytes = Vec::new();
+        open_screen_device(self, "vcs", false)?.read_to_end(&mut bytes)?;
+        if cols == 0 {
+            return Ok(Vec::new());
+        }
+        Ok(bytes.chunks(cols)
+            .map(|row| row.iter().map(|b| *b as char).collect::<String>().trim_end_matches(' ').to_string())
+            .collect())
+    }
+
+    /// Captures the contents of the screen, together with the attributes of each cell
+    /// and the position of the cursor, reading them from `/dev/vcsa<N>`.
+    pub fn snapshot(&self) -> Result<ScreenSnapshot> {
+        let mut file = open_vcsa(self, false)?;
+        let header = read_vcsa_header(&mut file)?;
+
+        let mut data = vec![0u8; header.rows as usize * header.cols as usize * 2];
+        file.read_exact(&mut data)?;
+        let cells = data.chunks(2)
+            .map(|cell| Cell {
+                glyph: cell[0],
+                attribute: CellAttribute::from_byte(cell[1])
+            })
+            .collect();
+
+        Ok(ScreenSnapshot {
+            rows: header.rows as u16,
+            cols: header.cols as u16,
+            cursor: (header.cursor_y as u16, header.cursor_x as u16),
+            cells
+        })
+    }
+
+    /// Writes the contents of a snapshot back to the screen, and moves the cursor to where it was,
+    /// writing directly through `/dev/vcsa<N>`.
+    /// 
+    /// Returns an error of kind [`InvalidInput`] if the size of the screen changed since the snapshot was taken.
+    /// 
+    /// Returns `self` for chaining.
+    /// 
+    /// [`InvalidInput`]: std::io::ErrorKind::InvalidInput
+    pub fn restore_snapshot(&mut self, snapshot: &ScreenSnapshot) -> Result<&mut Self> {
+        let mut file = open_vcsa(self, true)?;
+        let header = read_vcsa_header(&mut file)?;
+        if header.rows as u16 != snapshot.rows || header.cols as u16 != snapshot.cols {
+            return Err(VtError::new(
+                io::ErrorKind::InvalidInput,
+                format!("Cannot restore a {}x{} snapshot on a {}x{} screen", snapshot.rows, snapshot.cols, header.rows, header.cols)
+            ));
+        }
+
+        // The kernel ignores writes to the geometry in the header, but moves the cursor
+        // when the cursor position is written.
+        let mut data = Vec::with_capacity(4 + snapshot.cells.len() * 2);
+        data.extend_from_slice(&[header.rows, header.cols, snapshot.cursor.1 as u8, snapshot.cursor.0 as u8]);
+        for cell in &snapshot.cells {
+            data.push(cell.glyph);
+            data.push(cell.attribute.as_byte());
+        }
+
+        file.seek(SeekFrom::Start(0))?;
+        file.write_all(&data)?;
+
+        Ok(self)
+    }
+
+    /// Switches to the alternate screen buffer, returning a guard which switches back when dropped,
+    /// so that full-screen applications can restore what was on the screen when they exit.
+    /// The guard dereferences to the terminal itself.
+    /// 
+    /// This uses the `ESC [ ? 1049 h` escape sequence. Since older kernels ignore it, a [`ScreenSnapshot`]
+    /// is also taken when possible, and restored when leaving: this needs access to `/dev/vcsa<N>`,
+    /// without which the screen is restored only on kernels supporting the alternate screen.
+    /// 
+    /// [`ScreenSnapshot`]: crate::ScreenSnapshot
+    pub fn enter_alternate_screen(&mut self) -> Result<AlternateScreenGuard<'_, 'a>> {
+        let snapshot = self.snapshot().ok();
+        self.write_all(b"\x1b[?1049h")?;
+        Ok(AlternateScreenGuard {
+            vt: self,
+            snapshot,
+            active: true
+        })
+    }
+
+    /// Selects the text between two positions of the screen, highlighting it as `gpm` does.
+    /// Positions are given as `(row, col)` and are 0-based. The selected text can be pasted with [`Vt::paste_selection`].
+    /// 
+    /// The kernel always applies the selection to the active terminal, so this terminal should be active.
+    /// Selecting text usually requires the `CAP_SYS_ADMIN` capability.
+    /// 
+    /// Returns an error of kind [`InvalidInput`] if a position is outside of the screen.
+    /// 
+    /// Returns `self` for chaining.
+    /// 
+    /// [`Vt::paste_selection`]: crate::Vt::paste_selection
+    /// [`InvalidInput`]: std::io::ErrorKind::InvalidInput
+    pub fn set_selection(&mut self, start: (u16, u16), end: (u16, u16), mode: SelectionMode) -> Result<&mut Self> {
+        let (rows, cols) = self.size()?;
+        for &(row, col) in &[start, end] {
+            if row >= rows || col >= cols {
+                return Err(VtError::new(io::ErrorKind::InvalidInput, format!("Position outside of the screen: ({}, {})", row, col)));
+            }
+        }
+        let selection = ffi::TioclSelection {
+            subcode: ffi::TIOCL_SETSEL,
+            xs: start.1 + 1,
+            ys: start.0 + 1,
+            xe: end.1 + 1,
+            ye: end.0 + 1,
+            sel_mode: match mode {
+                SelectionMode::Char => ffi::TIOCL_SELCHAR,
+                SelectionMode::Word => ffi::TIOCL_SELWORD,
+                SelectionMode::Line => ffi::TIOCL_SELLINE,
+                SelectionMode::Pointer => ffi::TIOCL_SELPOINTER,
+                SelectionMode::Clear => ffi::TIOCL_SELCLEAR
+            }
+        };
+        ffi::tioclinux_setsel(self.as_raw_fd(), &selection)?;
+        Ok(self)
+    }
+
+    /// Scrolls the console by the given number of lines: negative values scroll back into the history,
+    /// as `Shift + PageUp` does, and positive values scroll forward towards the most recent output.
+    /// 
+    /// The kernel always scrolls the active terminal, so this terminal should be active.
+    /// Note that the console driver might not keep any scrollback, in which case this does nothing.
+    /// 
+    /// Returns `self` for chaining.
+    pub fn scroll(&mut self, lines: i32) -> Result<&mut Self> {
+        // The kernel scrolls by half a screen when asked to scroll by 0 lines
+        if lines != 0 {
+            let mut arg: [c_int; 2] = [ffi::TIOCL_SCROLL, lines];
+            ffi::tioclinux(self.as_raw_fd(), arg.as_mut_ptr())?;
+        }
+        Ok(self)
+    }
+
+    /// Injects the current console selection into the input of this terminal, as if it had been typed.
+    /// 
+    /// Returns `self` for chaining.
+    pub fn paste_selection(&mut self) -> Result<&mut Self> {
+        let mut arg = ffi::TIOCL_PASTESEL;
+        ffi::tioclinux(self.as_raw_fd(), &mut arg)?;
+        Ok(self)
+    }
+
+    /// Returns the position of the cursor as `(x, y)`, reading it from the header of `/dev/vcsa<N>`.
+    /// 
+    /// Unlike querying the terminal with escape sequences, this does not require any round-trip
+    /// through the terminal, and returns the position known by the kernel.
+    /// Note that coordinates are 0-based, as reported by the kernel.
+    pub fn cursor_position_from_vcsa(&self) -> Result<(u8, u8)> {
+        let mut file = open_vcsa(self, false)?;
+        let header = read_vcsa_header(&mut file)?;
+        Ok((header.cursor_x, header.cursor_y))
+    }
+
+    /// Returns the attribute of the cell at the given position, reading it from `/dev/vcsa<N>`.
+    /// Coordinates are 0-based.
+    /// 
+    /// Returns an error of kind [`InvalidInput`] if the position is outside of the screen.
+    /// 
+    /// [`InvalidInput`]: std::io::ErrorKind::InvalidInput
+    pub fn attribute_at(&self, row: u16, col: u16) -> Result<CellAttribute> {
+        let mut file = open_vcsa(self, false)?;
+        let header = read_vcsa_header(&mut file)?;
+        if row >= header.rows as u16 || col >= header.cols as u16 {
+            return Err(VtError::new(
+                io::ErrorKind::InvalidInput,
+                format!("Position ({}, {}) is outside of the {}x{} screen", row, col, header.rows, header.cols)
+            ));
+        }
+
+        let offset = 4 + (row as u64 * header.cols as u64 + col as u64) * 2 + 1;
+        let mut attr = [0u8; 1];
+        file.seek(SeekFrom::Start(offset))?;
+        file.read_exact(&mut attr)?;
+
+        Ok(CellAttribute::from_byte(attr[0]))
+    }
+
+    /// Applies the given attribute to every cell of the screen, writing it directly through `/dev/vcsa<N>`.
+    /// 
+    /// Unlike escape sequences, this works regardless of the state of the terminal,
+    /// for example when it is in graphics mode or its output is being redirected.
+    /// Note that this only recolors what is currently on the screen: `/dev/vcsa<N>` gives no access
+    /// to the default attribute used by the kernel for new output, which can only be changed
+    /// with the `ESC [ 8 ]` escape sequence.
+    /// 
+    /// Returns `self` for chaining.
+    pub fn fill_screen_attribute(&mut self, attr: CellAttribute) -> Result<&mut Self> {
+        let mut file = open_vcsa(self, true)?;
+        let header = read_vcsa_header(&mut file)?;
+
+        let mut cells = vec![0u8; header.rows as usize * header.cols as usize * 2];
+        file.read_exact(&mut cells)?;
+        for cell in cells.chunks_mut(2) {
+            cell[1] = attr.as_byte();
+        }
+
+        file.seek(SeekFrom::Start(4))?;
+        file.write_all(&cells)?;
+
+        Ok(self)
+    }
+
+}
+
+/// Guard returned by [`Vt::enter_alternate_screen`], which leaves the alternate screen when dropped.
+/// 
+/// [`Vt::enter_alternate_screen`]: crate::Vt::enter_alternate_screen
+pub struct AlternateScreenGuard<'v, 'a> {
+    vt: &'v mut Vt<'a>,
+    snapshot: Option<ScreenSnapshot>,
+    active: bool
+}
+
+impl<'v, 'a> AlternateScreenGuard<'v, 'a> {
+
+    /// Leaves the alternate screen and restores the previous contents of the screen,
+    /// reporting any error, which is instead ignored when the guard is dropped.
+    pub fn leave_alternate_screen(mut self) -> Result<()> {
+        self.leave()
+    }
+
+    fn leave(&mut self) -> Result<()> {
+        if !self.active {
+            return Ok(());
+        }
+        self.active = false;
+        self.vt.write_all(b"\x1b[?1049l")?;
+        if let Some(ref snapshot) = self.snapshot {
+            self.vt.restore_snapshot(snapshot)?;
+        }
+        Ok(())
+    }
+
+}
+
+impl<'v, 'a> Deref for AlternateScreenGuard<'v, 'a> {
+    type Target = Vt<'a>;
+
+    fn deref(&self) -> &Vt<'a> {
+        self.vt
+    }
+}
+
+impl<'v, 'a> DerefMut for AlternateScreenGuard<'v, 'a> {
+    fn deref_mut(&mut self) -> &mut Vt<'a> {
+        self.vt
+    }
+}
+
+impl<'v, 'a> Drop for AlternateScreenGuard<'v, 'a> {
+    fn drop(&mut self) {
+        let _ = self.leave();
+    }
+}
@@ -0,0 +1,234 @@
+use std::io::{self, Write};
+use std::fs::OpenOptions;
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::os::unix::io::AsRawFd;
+use std::time::SystemTime;
+use nix::libc::{self, c_char, TIOCSCTTY};
+use crate::ffi;
+use crate::error::{Result, VtError};
+use crate::console::Console;
+use crate::vt::Vt;
+
+const WTMP_PATH: &str = "/var/log/wtmp";
+const SYSRQ_TRIGGER_PATH: &str = "/proc/sysrq-trigger";
+
+/// Starts a new session with the calling process as its leader, detaching it from its controlling terminal.
+/// Returns the id of the new session.
+/// 
+/// This fails if the process is already a process group leader. After this, a terminal can be adopted
+/// as controlling terminal with [`Vt::make_controlling_terminal`].
+/// 
+/// [`Vt::make_controlling_terminal`]: crate::Vt::make_controlling_terminal
+pub fn setsid() -> Result<i32> {
+    nix::unistd::setsid()
+        .map(|sid| sid.as_raw())
+        .map_err(VtError::from)
+}
+
+/// Configuration for [`Vt::spawn_session`].
+/// 
+/// [`Vt::spawn_session`]: crate::Vt::spawn_session
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct SessionConfig {
+    /// If set, a login record for this user is added to utmp and wtmp when the session starts,
+    /// so that tools like `who` and `last` report it, and is closed when the session ends.
+    /// Writing the records usually requires root privileges.
+    pub utmp_user: Option<String>,
+    /// Remote host recorded in the login record, if any.
+    pub utmp_host: Option<String>
+}
+
+/// A process spawned on a terminal with [`Vt::spawn_session`].
+/// 
+/// If a login record has been written for the session, it is closed when the process is waited for.
+/// 
+/// [`Vt::spawn_session`]: crate::Vt::spawn_session
+pub struct Session {
+    child: Child,
+    record: Option<libc::utmpx>
+}
+
+impl Session {
+
+    /// Returns the id of the session leader process.
+    pub fn id(&self) -> u32 {
+        self.child.id()
+    }
+
+    /// Returns the underlying child process.
+    pub fn child_mut(&mut self) -> &mut Child {
+        &mut self.child
+    }
+
+    /// Waits for the session leader to exit, closing its login record if there is one.
+    pub fn wait(&mut self) -> Result<ExitStatus> {
+        let status = self.child.wait()?;
+        if let Some(record) = self.record.take() {
+            let record = dead_record(record);
+            write_utmp(&record)?;
+            append_wtmp(&record)?;
+        }
+        Ok(status)
+    }
+
+    /// Kills the session leader and reaps it, ignoring errors since the process might have already exited.
+    fn abort(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+
+}
+
+impl Console {
+
+    /// Triggers the Secure Attention Key on the active terminal, as if the key bound to it had been pressed.
+    /// 
+    /// The kernel kills all the processes with the active terminal open, including the caller if it has it open,
+    /// and resets the terminal, so that the next program started on it (usually a fresh `getty` spawned by `init`)
+    /// is guaranteed to be talking to the user without any other program listening in: this is the basis
+    /// of a trusted path for login screens. Sessions spawned on the terminal with [`Vt::spawn_session`]
+    /// are terminated as well, and their login records should be closed by waiting for them.
+    /// 
+    /// This uses `/proc/sysrq-trigger`, which requires root privileges but works even if SysRq keys are disabled.
+    /// To let users trigger SAK from the keyboard instead, bind a key to [`KEY_SAK`] in the keymap.
+    /// 
+    /// [`Vt::spawn_session`]: crate::Vt::spawn_session
+    /// [`KEY_SAK`]: crate::KEY_SAK
+    pub fn trigger_sak(&self) -> Result<()> {
+        OpenOptions::new().write(true).open(SYSRQ_TRIGGER_PATH)?.write_all(b"k")?;
+        Ok(())
+    }
+
+}
+
+impl<'a> Vt<'a> {
+
+    /// Makes this terminal the controlling terminal of the calling process, which must be a session leader
+    /// without a controlling terminal (see [`setsid`]). The process then receives `SIGHUP` when the terminal hangs up,
+    /// and job control signals from the keyboard.
+    /// 
+    /// If the terminal is already the controlling terminal of another session, this fails unless `force` is set,
+    /// in which case the terminal is stolen from the other session. Forcing requires the `CAP_SYS_ADMIN` capability.
+    /// 
+    /// [`setsid`]: crate::setsid
+    pub fn make_controlling_terminal(&self, force: bool) -> Result<()> {
+        ffi::tiocsctty(self.as_raw_fd(), force as _)
+    }
+
+    /// Spawns `command` attached to this terminal, as `getty` and display managers do.
+    /// 
+    /// The child process starts a new session, with this terminal as its controlling terminal
+    /// and as its standard input, output and error. Any stdio redirection configured on `command` is overridden.
+    pub fn spawn(&self, mut command: Command) -> Result<Child> {
+        command
+            .stdin(Stdio::from(self.file().try_clone()?))
+            .stdout(Stdio::from(self.file().try_clone()?))
+            .stderr(Stdio::from(self.file().try_clone()?));
+
+        // This runs in the child after the terminal has been duplicated onto the standard streams,
+        // so only async-signal-safe functions can be used.
+        unsafe {
+            command.pre_exec(|| {
+                if libc::setsid() == -1 || libc::ioctl(0, TIOCSCTTY as _, 0) == -1 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+
+        Ok(command.spawn()?)
+    }
+
+    /// Spawns `command` attached to this terminal like [`Vt::spawn`] does,
+    /// also managing the login records as specified by `config`.
+    /// 
+    /// If the login records cannot be written, the process is killed and waited for before returning the error,
+    /// and any record already written is closed. Like `login` does, a missing wtmp file is not an error.
+    /// 
+    /// [`Vt::spawn`]: crate::Vt::spawn
+    pub fn spawn_session(&self, command: Command, config: &SessionConfig) -> Result<Session> {
+        let child = self.spawn(command)?;
+        let mut session = Session {
+            child,
+            record: None
+        };
+
+        if let Some(ref user) = config.utmp_user {
+            let line = format!("tty{}", self.number());
+            let mut record: libc::utmpx = unsafe { std::mem::zeroed() };
+            record.ut_type = libc::USER_PROCESS;
+            record.ut_pid = session.id() as libc::pid_t;
+            copy_c_string(&mut record.ut_line, &line);
+            copy_c_string(&mut record.ut_id, &self.number().to_string());
+            copy_c_string(&mut record.ut_user, user);
+            copy_c_string(&mut record.ut_host, config.utmp_host.as_deref().unwrap_or(""));
+            set_record_time(&mut record);
+
+            // Do not leave the process running without a handle to it
+            if let Err(e) = write_utmp(&record) {
+                session.abort();
+                return Err(e);
+            }
+            if let Err(e) = append_wtmp(&record) {
+                session.abort();
+                let _ = write_utmp(&dead_record(record));
+                return Err(e);
+            }
+            session.record = Some(record);
+        }
+
+        Ok(session)
+    }
+
+}
+
+/// Copies `src` to the fixed size field `dst`, truncating it if needed.
+fn copy_c_string(dst: &mut [c_char], src: &str) {
+    for (d, s) in dst.iter_mut().zip(src.bytes().chain(std::iter::repeat(0))) {
+        *d = s as c_char;
+    }
+}
+
+fn set_record_time(record: &mut libc::utmpx) {
+    let now = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default();
+    record.ut_tv.tv_sec = now.as_secs() as _;
+    record.ut_tv.tv_usec = now.subsec_micros() as _;
+}
+
+/// Turns a login record into the one closing it.
+fn dead_record(mut record: libc::utmpx) -> libc::utmpx {
+    record.ut_type = libc::DEAD_PROCESS;
+    copy_c_string(&mut record.ut_user, "");
+    copy_c_string(&mut record.ut_host, "");
+    set_record_time(&mut record);
+    record
+}
+
+/// Updates utmp with the given record.
+fn write_utmp(record: &libc::utmpx) -> Result<()> {
+    unsafe {
+        libc::setutxent();
+        let res = if libc::pututxline(record).is_null() {
+            Err(io::Error::last_os_error())
+        } else {
+            Ok(())
+        };
+        libc::endutxent();
+        res?;
+    }
+    Ok(())
+}
+
+/// Appends the given record to wtmp. Systems without wtmp are silently skipped.
+fn append_wtmp(record: &libc::utmpx) -> Result<()> {
+    let bytes = unsafe {
+        std::slice::from_raw_parts(record as *const libc::utmpx as *const u8, std::mem::size_of::<libc::utmpx>())
+    };
+    match OpenOptions::new().append(true).open(WTMP_PATH) {
+        Ok(mut file) => file.write_all(bytes)?,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => {},
+        Err(e) => return Err(e.into())
+    }
+    Ok(())
+}
@@ -0,0 +1,150 @@
+use std::fmt;
+use std::io::Write;
+use crate::error::Result;
+use crate::vt::Vt;
+
+/// One of the 16 colors supported by the Linux console.
+/// 
+/// The actual colors displayed depend on the palette of the console.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum Color {
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+    BrightBlack,
+    BrightRed,
+    BrightGreen,
+    BrightYellow,
+    BrightBlue,
+    BrightMagenta,
+    BrightCyan,
+    BrightWhite
+}
+
+impl Color {
+
+    /// Returns the index of the color in the palette (`0-15`).
+    pub fn index(self) -> u8 {
+        self as u8
+    }
+
+    /// Returns the color with the given index in the palette, or `None` if the index is out of range.
+    pub fn from_index(index: u8) -> Option<Color> {
+        use Color::*;
+        const COLORS: [Color; 16] = [
+            Black, Red, Green, Yellow, Blue, Magenta, Cyan, White,
+            BrightBlack, BrightRed, BrightGreen, BrightYellow, BrightBlue, BrightMagenta, BrightCyan, BrightWhite
+        ];
+        COLORS.get(index as usize).copied()
+    }
+
+}
+
+/// Colors and attributes of text, as supported by the Linux console.
+/// 
+/// Unset colors use the default ones of the terminal. Since the console only has 8 background colors,
+/// bright background colors are rendered with the blink attribute, which most consoles show as a bright background.
+/// Likewise, bright foreground colors imply bold text.
+/// 
+/// Use [`Vt::write_styled`] to write text with a style, or format the style with [`Display`]
+/// to get the escape sequence setting it.
+/// 
+/// [`Vt::write_styled`]: crate::Vt::write_styled
+/// [`Display`]: std::fmt::Display
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug, Default)]
+pub struct Style {
+    /// Color of the text.
+    pub foreground: Option<Color>,
+    /// Color of the background.
+    pub background: Option<Color>,
+    /// Whether the text is bold.
+    pub bold: bool,
+    /// Whether the text is underlined.
+    pub underline: bool,
+    /// Whether foreground and background colors are swapped.
+    pub reverse: bool
+}
+
+impl Style {
+
+    /// Creates a new style with the default colors and no attributes.
+    pub fn new() -> Style {
+        Style::default()
+    }
+
+    /// Returns a copy of this style with the given foreground color.
+    pub fn with_foreground(self, color: Color) -> Style {
+        Style { foreground: Some(color), ..self }
+    }
+
+    /// Returns a copy of this style with the given background color.
+    pub fn with_background(self, color: Color) -> Style {
+        Style { background: Some(color), ..self }
+    }
+
+    /// Returns a copy of this style with bold text.
+    pub fn with_bold(self) -> Style {
+        Style { bold: true, ..self }
+    }
+
+    /// Returns a copy of this style with underlined text.
+    pub fn with_underline(self) -> Style {
+        Style { underline: true, ..self }
+    }
+
+    /// Returns a copy of this style with reversed colors.
+    pub fn with_reverse(self) -> Style {
+        Style { reverse: true, ..self }
+    }
+
+}
+
+/// Formats the SGR escape sequence applying the style, starting from a reset of all the attributes.
+impl fmt::Display for Style {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("\x1b[0")?;
+        if self.bold {
+            f.write_str(";1")?;
+        }
+        if self.underline {
+            f.write_str(";4")?;
+        }
+        if self.reverse {
+            f.write_str(";7")?;
+        }
+        if let Some(color) = self.foreground {
+            let index = color.index();
+            if index >= 8 && !self.bold {
+                f.write_str(";1")?;
+            }
+            write!(f, ";{}", 30 + (index & 7))?;
+        }
+        if let Some(color) = self.background {
+            let index = color.index();
+            if index >= 8 {
+                f.write_str(";5")?;
+            }
+            write!(f, ";{}", 40 + (index & 7))?;
+        }
+        f.write_str("m")
+    }
+}
+
+impl<'a> Vt<'a> {
+
+    /// Writes the given text with the given style, resetting the attributes afterwards.
+    /// The text is written with a single call, so that it does not interleave with other writers.
+    /// 
+    /// Returns `self` for chaining.
+    pub fn write_styled(&mut self, text: &str, style: &Style) -> Result<&mut Self> {
+        let out = format!("{}{}\x1b[0m", style, text);
+        self.write_all(out.as_bytes())?;
+        Ok(self)
+    }
+
+}
@@ -0,0 +1,195 @@
+use std::ops::{Deref, DerefMut};
+use std::os::unix::io::AsRawFd;
+use nix::libc::c_int;
+use nix::sys::signal::Signal;
+use crate::ffi;
+use crate::error::Result;
+use crate::console::Console;
+use crate::vt::{Vt, VtNumber, AsVtNumber};
+
+/// Configuration for [`Console::take_over_vt`].
+/// 
+/// [`Console::take_over_vt`]: crate::Console::take_over_vt
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct TakeoverConfig {
+    /// Whether to put the terminal in graphics mode, so that the kernel stops drawing the text console.
+    pub graphics: bool,
+    /// If set, the terminal is put in process-controlled switching mode (`VT_PROCESS`), and the kernel
+    /// sends the first signal when the user wants to switch away and the second one when switching back.
+    /// The process is then responsible for acknowledging the switches.
+    pub switch_signals: Option<(Signal, Signal)>,
+    /// Whether to lock terminal switching for the whole duration of the takeover.
+    pub lock_switch: bool
+}
+
+impl Default for TakeoverConfig {
+    fn default() -> TakeoverConfig {
+        TakeoverConfig {
+            graphics: true,
+            switch_signals: None,
+            lock_switch: false
+        }
+    }
+}
+
+/// Guard returned by [`Console::take_over_vt`], which restores the console when dropped.
+/// 
+/// The guard dereferences to the [`Vt`] that has been taken over.
+/// When dropped, it undoes the takeover in reverse order:
+/// 
+/// 1. switching is unlocked, if it was locked;
+/// 2. the previous display mode (usually text mode) is restored;
+/// 3. the previous switching mode (usually automatic) is restored;
+/// 4. the terminal that was active before the takeover is switched back to.
+/// 
+/// Only the steps actually performed are undone, so a takeover that fails halfway through
+/// leaves the console as it was before.
+/// 
+/// [`Console::take_over_vt`]: crate::Console::take_over_vt
+/// [`Vt`]: crate::Vt
+pub struct VtTakeover<'a> {
+    vt: Vt<'a>,
+    previous_vt: VtNumber,
+    previous_kd_mode: Option<c_int>,
+    previous_vt_mode: Option<ffi::VtMode>,
+    locked: bool
+}
+
+/// Guard returned by [`Console::switch_guard`], which switches back to the terminal
+/// that was active before, when dropped or if a panic unwinds past it.
+/// 
+/// If switching has been locked with [`SwitchGuard::lock_switch`], it is unlocked first.
+/// 
+/// [`Console::switch_guard`]: crate::Console::switch_guard
+/// [`SwitchGuard::lock_switch`]: crate::SwitchGuard::lock_switch
+pub struct SwitchGuard<'a> {
+    console: &'a Console,
+    previous_vt: VtNumber,
+    locked: bool
+}
+
+impl<'a> SwitchGuard<'a> {
+
+    /// Returns the terminal that will be switched back to.
+    pub fn previous_vt(&self) -> VtNumber {
+        self.previous_vt
+    }
+
+    /// Locks terminal switching until the guard is dropped.
+    /// 
+    /// Returns `self` for chaining.
+    pub fn lock_switch(&mut self) -> Result<&mut Self> {
+        self.console.lock_switch(true)?;
+        self.locked = true;
+        Ok(self)
+    }
+
+}
+
+impl Console {
+
+    /// Switches to the given terminal, returning a guard that switches back
+    /// to the currently active one when dropped.
+    pub fn switch_guard<N: AsVtNumber>(&self, target: N) -> Result<SwitchGuard<'_>> {
+        let guard = SwitchGuard {
+            console: self,
+            previous_vt: self.current_vt_number()?,
+            locked: false
+        };
+        self.switch_to(target)?;
+        Ok(guard)
+    }
+
+    /// Takes over a terminal for exclusive graphical use, as display servers and compositors do.
+    /// 
+    /// If `vt_number` is `None`, a new terminal is allocated, otherwise the given one is opened.
+    /// The terminal is then activated and configured as specified by `config`.
+    /// Everything is restored when the returned guard is dropped: see [`VtTakeover`] for the details.
+    /// 
+    /// [`VtTakeover`]: crate::VtTakeover
+    pub fn take_over_vt(&self, vt_number: Option<VtNumber>, config: TakeoverConfig) -> Result<VtTakeover<'_>> {
+        let vt = match vt_number {
+            Some(n) => self.open_vt(n)?,
+            None => self.new_vt()?
+        };
+
+        let mut takeover = VtTakeover {
+            previous_vt: self.current_vt_number()?,
+            vt,
+            previous_kd_mode: None,
+            previous_vt_mode: None,
+            locked: false
+        };
+        takeover.vt.switch()?;
+
+        let fd = takeover.vt.as_raw_fd();
+        if config.graphics {
+            let mode = ffi::kdgetmode(fd)?;
+            ffi::kdsetmode(fd, ffi::KD_GRAPHICS)?;
+            takeover.previous_kd_mode = Some(mode);
+        }
+
+        if let Some((release, acquire)) = config.switch_signals {
+            let mode = ffi::vt_getmode(fd)?;
+            let new_mode = ffi::VtMode {
+                mode: ffi::VT_PROCESS,
+                waitv: 0,
+                relsig: release as _,
+                acqsig: acquire as _,
+                frsig: 0
+            };
+            ffi::vt_setmode(fd, &new_mode)?;
+            takeover.previous_vt_mode = Some(mode);
+        }
+
+        if config.lock_switch {
+            self.lock_switch(true)?;
+            takeover.locked = true;
+        }
+
+        Ok(takeover)
+    }
+
+}
+
+impl<'a> Drop for SwitchGuard<'a> {
+    fn drop(&mut self) {
+        // Errors are ignored, since we want to restore as much as possible
+        if self.locked {
+            let _ = self.console.lock_switch(false);
+        }
+        let _ = self.console.switch_to(self.previous_vt);
+    }
+}
+
+impl<'a> Deref for VtTakeover<'a> {
+    type Target = Vt<'a>;
+
+    fn deref(&self) -> &Vt<'a> {
+        &self.vt
+    }
+}
+
+impl<'a> DerefMut for VtTakeover<'a> {
+    fn deref_mut(&mut self) -> &mut Vt<'a> {
+        &mut self.vt
+    }
+}
+
+impl<'a> Drop for VtTakeover<'a> {
+    fn drop(&mut self) {
+        // Errors are ignored, since we want to restore as much as possible
+        let console = self.vt.console();
+        let fd = self.vt.as_raw_fd();
+        if self.locked {
+            let _ = console.lock_switch(false);
+        }
+        if let Some(mode) = self.previous_kd_mode {
+            let _ = ffi::kdsetmode(fd, mode);
+        }
+        if let Some(mode) = self.previous_vt_mode {
+            let _ = ffi::vt_setmode(fd, &mode);
+        }
+        let _ = console.switch_to(self.previous_vt);
+    }
+}
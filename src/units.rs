@@ -0,0 +1,27 @@
+//! Conversions between `Duration` and the units used by the various kernel timers.
+
+use std::io;
+use std::time::Duration;
+use crate::error::{Result, VtError};
+
+/// Converts the given duration to deciseconds, rounding up so that non-zero durations
+/// never become zero. Fails if the result does not fit in `max`.
+pub(crate) fn decisecs_from_duration(d: Duration, max: u32) -> Result<u32> {
+    let decisecs = d.as_millis().div_ceil(100);
+    check_range(decisecs, max, "deciseconds")
+}
+
+/// Converts the given duration to minutes, rounding up so that non-zero durations
+/// never become zero. Fails if the result does not fit in `max`.
+pub(crate) fn minutes_from_duration(d: Duration, max: u32) -> Result<u32> {
+    let minutes = d.as_millis().div_ceil(60_000);
+    check_range(minutes, max, "minutes")
+}
+
+fn check_range(value: u128, max: u32, unit: &str) -> Result<u32> {
+    if value > max as u128 {
+        Err(VtError::new(io::ErrorKind::InvalidInput, format!("Duration too long: {} {} exceeds the maximum of {}", value, unit, max)))
+    } else {
+        Ok(value as u32)
+    }
+}
@@ -1,14 +1,24 @@
 use std::io::{self, Write, Read, IoSlice, IoSliceMut};
+use std::convert::TryFrom;
 use std::fmt;
+use std::mem::ManuallyDrop;
+use std::ops::{Deref, DerefMut};
+use std::time::{Duration, Instant, SystemTime};
 use std::fs::{File, OpenOptions};
-use std::os::unix::io::{RawFd, AsRawFd};
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::io::{RawFd, AsRawFd, AsFd, BorrowedFd, OwnedFd};
 use nix::libc::*;
+use nix::unistd::{Pid, tcgetpgrp, tcsetpgrp};
+use nix::sys::signal::Signal;
 use nix::sys::termios::{
-    Termios, InputFlags, LocalFlags, FlushArg, SetArg, SpecialCharacterIndices,
-    tcgetattr, tcsetattr, tcflush, cfmakeraw
+    Termios, InputFlags, OutputFlags, LocalFlags, FlushArg, SetArg, SpecialCharacterIndices, BaudRate,
+    tcgetattr, tcsetattr, tcflush, tcdrain, cfmakeraw, cfgetospeed, cfsetspeed
 };
 use crate::ffi;
-use crate::console::Console;
+use crate::units;
+use crate::error::{Result, VtError};
+use crate::console::{Console, ConsoleRef};
+use crate::input::{self, KeyEvent, RawKeyEvent};
 
 /// A trait to extract the raw terminal number from an object.
 pub trait AsVtNumber {
@@ -25,12 +35,26 @@ pub struct VtNumber(i32);
 impl VtNumber {
 
     /// Creates a new `VtNumber` for the given integer.
-    /// Panics if the number is negative.
+    /// Panics if the number is negative or greater than the maximum supported by the kernel (63):
+    /// use [`VtNumber::try_new`] for numbers that come from untrusted sources.
+    /// 
+    /// [`VtNumber::try_new`]: crate::VtNumber::try_new
     pub fn new(number: i32) -> VtNumber {
-        if number < 0 {
-            panic!("Invalid virtual terminal number.");
+        match VtNumber::try_new(number) {
+            Ok(n) => n,
+            Err(_) => panic!("Invalid virtual terminal number.")
+        }
+    }
+
+    /// Creates a new `VtNumber` for the given integer, returning [`VtError::InvalidVtNumber`]
+    /// if the number is negative or greater than the maximum supported by the kernel (63).
+    /// 
+    /// [`VtError::InvalidVtNumber`]: crate::VtError::InvalidVtNumber
+    pub fn try_new(number: i32) -> Result<VtNumber> {
+        if !(0..=ffi::MAX_NR_CONSOLES).contains(&number) {
+            return Err(VtError::InvalidVtNumber(number));
         }
-        VtNumber(number)
+        Ok(VtNumber(number))
     }
 
     pub(crate) fn as_native(self) -> c_int {
@@ -39,9 +63,11 @@ impl VtNumber {
 
 }
 
-impl From<i32> for VtNumber {
-    fn from(number: i32) -> VtNumber {
-        VtNumber::new(number)
+impl TryFrom<i32> for VtNumber {
+    type Error = VtError;
+
+    fn try_from(number: i32) -> Result<VtNumber> {
+        VtNumber::try_new(number)
     }
 }
 
@@ -69,6 +95,166 @@ bitflags! {
     }
 }
 
+bitflags! {
+    /// Keyboard LEDs of a virtual terminal.
+    /// Use [`Vt::set_leds`] to drive the LEDs of a virtual terminal.
+    /// 
+    /// [`Vt::set_leds`]: crate::Vt::set_leds
+    pub struct VtLeds: u8 {
+        const SCROLL_LOCK = 1;
+        const NUM_LOCK    = 1 << 1;
+        const CAPS_LOCK   = 1 << 2;
+    }
+}
+
+bitflags! {
+    /// Lock keys of a virtual terminal, which affect how keys are translated.
+    /// Use [`Vt::locks`] to read them, and [`Vt::set_locks`] to change them.
+    /// 
+    /// [`Vt::locks`]: crate::Vt::locks
+    /// [`Vt::set_locks`]: crate::Vt::set_locks
+    pub struct KeyboardLocks: u8 {
+        const SCROLL_LOCK = 1;
+        const NUM_LOCK    = 1 << 1;
+        const CAPS_LOCK   = 1 << 2;
+    }
+}
+
+bitflags! {
+    /// Processing applied by a virtual terminal to the output, before displaying it.
+    /// Use [`Vt::set_output_processing`] to change it.
+    /// 
+    /// [`Vt::set_output_processing`]: crate::Vt::set_output_processing
+    pub struct OutputProcessing: u8 {
+        /// Enables output processing (`OPOST`): without this, the other flags have no effect.
+        const ENABLED    = 1;
+        /// Translates `\n` to `\r\n` (`ONLCR`).
+        const NL_TO_CRNL = 1 << 1;
+        /// Translates `\r` to `\n` (`OCRNL`).
+        const CR_TO_NL   = 1 << 2;
+    }
+}
+
+bitflags! {
+    /// Modifier keys currently held down, as returned by [`Vt::shift_state`].
+    /// 
+    /// [`Vt::shift_state`]: crate::Vt::shift_state
+    pub struct ShiftState: u8 {
+        const SHIFT       = 1;
+        const ALT_GR      = 1 << 1;
+        const CTRL        = 1 << 2;
+        const ALT         = 1 << 3;
+        const SHIFT_LEFT  = 1 << 4;
+        const SHIFT_RIGHT = 1 << 5;
+        const CTRL_LEFT   = 1 << 6;
+        const CTRL_RIGHT  = 1 << 7;
+    }
+}
+
+impl VtSignals {
+
+    const NAMES: [(&'static str, VtSignals); 3] = [
+        ("SIGINT", VtSignals::SIGINT),
+        ("SIGQUIT", VtSignals::SIGQUIT),
+        ("SIGTSTP", VtSignals::SIGTSTP)
+    ];
+
+    /// Builds a set of signals from their names (`SIGINT`, `SIGQUIT` or `SIGTSTP`).
+    /// Returns [`VtError::UnknownSignal`] if any of the names is not supported.
+    /// 
+    /// [`VtError::UnknownSignal`]: crate::VtError::UnknownSignal
+    pub fn from_names(names: &[&str]) -> Result<VtSignals> {
+        names.iter().try_fold(VtSignals::empty(), |signals, name| {
+            VtSignals::NAMES.iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, s)| signals | *s)
+                .ok_or_else(|| VtError::UnknownSignal(name.to_string()))
+        })
+    }
+
+    /// Returns the names of the signals in this set.
+    pub fn names(&self) -> Vec<&'static str> {
+        VtSignals::NAMES.iter()
+            .filter(|(_, s)| self.contains(*s))
+            .map(|(n, _)| *n)
+            .collect()
+    }
+
+}
+
+/// How switches from and to a virtual terminal are handled.
+/// Use [`Vt::set_switch_mode`] to change the mode of a terminal.
+/// 
+/// [`Vt::set_switch_mode`]: crate::Vt::set_switch_mode
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum SwitchMode {
+    /// Switches are handled automatically by the kernel.
+    Auto,
+    /// Switches are controlled by the process: the kernel sends `release` when the user
+    /// wants to switch away from the terminal and `acquire` when the terminal becomes active again.
+    /// The process must answer with [`Vt::release_display`] and [`Vt::acknowledge_acquire`] respectively.
+    /// 
+    /// [`Vt::release_display`]: crate::Vt::release_display
+    /// [`Vt::acknowledge_acquire`]: crate::Vt::acknowledge_acquire
+    Process {
+        release: Signal,
+        acquire: Signal
+    }
+}
+
+/// Display mode of a virtual terminal.
+/// Use [`Vt::set_kd_mode`] to change the mode of a terminal.
+/// 
+/// [`Vt::set_kd_mode`]: crate::Vt::set_kd_mode
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum KdMode {
+    /// The kernel draws the text console.
+    Text,
+    /// The kernel does not draw anything, leaving the screen to the application.
+    Graphics
+}
+
+/// Keyboard mode of a virtual terminal, determining what is delivered when reading from it.
+/// Use [`Vt::set_keyboard_mode`] to change the mode of a terminal.
+/// 
+/// [`Vt::set_keyboard_mode`]: crate::Vt::set_keyboard_mode
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum KeyboardMode {
+    /// Raw scancodes.
+    Raw,
+    /// Keycodes, with the highest bit set on release.
+    MediumRaw,
+    /// Characters translated according to the keymap, in the 8-bit charset.
+    Xlate,
+    /// Characters translated according to the keymap, encoded in UTF-8.
+    Unicode,
+    /// Nothing at all: keyboard input is ignored by the terminal.
+    Off
+}
+
+/// Shape of the cursor of a virtual terminal.
+/// Use [`Vt::set_cursor_shape`] to change it.
+/// 
+/// [`Vt::set_cursor_shape`]: crate::Vt::set_cursor_shape
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum CursorShape {
+    /// The default shape configured in the kernel, usually an underline.
+    Default,
+    Invisible,
+    Underline,
+    LowerThird,
+    LowerHalf,
+    TwoThirds,
+    Block,
+    /// A software cursor, drawn by changing the attribute of the cell under the cursor
+    /// instead of using the hardware cursor. The bits in `set` are set in the attribute,
+    /// and then the bits in `toggle` are flipped: for example, `toggle: 0x77` inverts the colors of the cell.
+    Software {
+        toggle: u8,
+        set: u8
+    }
+}
+
 /// Enum containing the VT buffers to flush.
 pub enum VtFlushType {
     Incoming,
@@ -76,17 +262,191 @@ pub enum VtFlushType {
     Both
 }
 
+/// What happens to a [`Vt`] when it is dropped. Use [`Vt::set_drop_policy`] to change it.
+/// 
+/// In every case, pending output is transmitted before the terminal is closed.
+/// 
+/// [`Vt`]: crate::Vt
+/// [`Vt::set_drop_policy`]: crate::Vt::set_drop_policy
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum DropPolicy {
+    /// Release the terminal, so that its number can be reused.
+    /// This is the default for terminals allocated with [`Console::new_vt`].
+    /// 
+    /// [`Console::new_vt`]: crate::Console::new_vt
+    Deallocate,
+    /// Just close the terminal, leaving it allocated and configured as it is.
+    /// This is the default for terminals opened with [`Console::open_vt`] or built from an existing file.
+    /// 
+    /// [`Console::open_vt`]: crate::Console::open_vt
+    KeepAllocated,
+    /// Restore the terminal attributes the terminal had when this `Vt` was created, leaving it allocated.
+    RestoreTermios,
+    /// If this terminal is active, switch back to the one that was active when this `Vt` was created,
+    /// then release the terminal as [`DropPolicy::Deallocate`] does.
+    /// 
+    /// [`DropPolicy::Deallocate`]: crate::DropPolicy::Deallocate
+    SwitchBack
+}
+
+/// Maximum time to wait for the rest of an escape sequence after an `ESC` has been read.
+const ESCAPE_SEQUENCE_TIMEOUT: Duration = Duration::from_millis(100);
+const CURSOR_REPORT_TIMEOUT: Duration = Duration::from_millis(500);
+
+const BAUD_RATES: [(u32, BaudRate); 31] = [
+    (0, BaudRate::B0), (50, BaudRate::B50), (75, BaudRate::B75), (110, BaudRate::B110),
+    (134, BaudRate::B134), (150, BaudRate::B150), (200, BaudRate::B200), (300, BaudRate::B300),
+    (600, BaudRate::B600), (1200, BaudRate::B1200), (1800, BaudRate::B1800), (2400, BaudRate::B2400),
+    (4800, BaudRate::B4800), (9600, BaudRate::B9600), (19200, BaudRate::B19200), (38400, BaudRate::B38400),
+    (57600, BaudRate::B57600), (115200, BaudRate::B115200), (230400, BaudRate::B230400), (460800, BaudRate::B460800),
+    (500000, BaudRate::B500000), (576000, BaudRate::B576000), (921600, BaudRate::B921600), (1000000, BaudRate::B1000000),
+    (1152000, BaudRate::B1152000), (1500000, BaudRate::B1500000), (2000000, BaudRate::B2000000), (2500000, BaudRate::B2500000),
+    (3000000, BaudRate::B3000000), (3500000, BaudRate::B3500000), (4000000, BaudRate::B4000000)
+];
+
+/// Default control characters, as set by `stty sane`.
+const SANE_CONTROL_CHARS: &[(SpecialCharacterIndices, u8)] = &[
+    (SpecialCharacterIndices::VINTR, 0x03),
+    (SpecialCharacterIndices::VQUIT, 0x1c),
+    (SpecialCharacterIndices::VERASE, 0x7f),
+    (SpecialCharacterIndices::VKILL, 0x15),
+    (SpecialCharacterIndices::VEOF, 0x04),
+    (SpecialCharacterIndices::VSTART, 0x11),
+    (SpecialCharacterIndices::VSTOP, 0x13),
+    (SpecialCharacterIndices::VSUSP, 0x1a),
+    (SpecialCharacterIndices::VREPRINT, 0x12),
+    (SpecialCharacterIndices::VWERASE, 0x17),
+    (SpecialCharacterIndices::VLNEXT, 0x16),
+    (SpecialCharacterIndices::VDISCARD, 0x0f),
+    (SpecialCharacterIndices::VEOL, 0),
+    (SpecialCharacterIndices::VEOL2, 0),
+    (SpecialCharacterIndices::VMIN, 1),
+    (SpecialCharacterIndices::VTIME, 0)
+];
+
+/// Operations allowed on a [`Vt`], depending on how its device was opened.
+/// 
+/// [`Vt`]: crate::Vt
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum VtAccess {
+    ReadOnly,
+    WriteOnly,
+    ReadWrite
+}
+
+impl VtAccess {
+
+    /// Returns whether reading is allowed.
+    pub fn is_readable(self) -> bool {
+        self != VtAccess::WriteOnly
+    }
+
+    /// Returns whether writing is allowed.
+    pub fn is_writable(self) -> bool {
+        self != VtAccess::ReadOnly
+    }
+
+}
+
+/// Declarative terminal configuration, applied with [`Vt::apply_config`] or [`Console::prepare_vt`].
+/// 
+/// The default configuration matches the one of the terminals allocated by [`Console::new_vt`]:
+/// canonical mode, no echo, no signals and no EOF character.
+/// 
+/// [`Vt::apply_config`]: crate::Vt::apply_config
+/// [`Console::prepare_vt`]: crate::Console::prepare_vt
+/// [`Console::new_vt`]: crate::Console::new_vt
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct TermiosConfig {
+    /// Whether the characters typed by the user are echoed.
+    pub echo: bool,
+    /// Whether input is delivered line by line.
+    pub canonical: bool,
+    /// Signals generated by the terminal. If empty, signal generation is disabled.
+    pub signals: VtSignals,
+    /// Whether `Ctrl + D` generates an EOF.
+    pub eof: bool
+}
+
+impl Default for TermiosConfig {
+    fn default() -> TermiosConfig {
+        TermiosConfig {
+            echo: false,
+            canonical: true,
+            signals: VtSignals::empty(),
+            eof: false
+        }
+    }
+}
+
+/// Size of the window of a virtual terminal, as returned by [`Vt::window_size`].
+/// 
+/// [`Vt::window_size`]: crate::Vt::window_size
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct WindowSize {
+    /// Number of rows, in characters.
+    pub rows: u16,
+    /// Number of columns, in characters.
+    pub cols: u16,
+    /// Width of the window, in pixels.
+    pub pixel_width: u16,
+    /// Height of the window, in pixels.
+    pub pixel_height: u16
+}
+
+/// Characters used by [`Vt::draw_progress_bar`] to render a progress bar.
+/// 
+/// [`Vt::draw_progress_bar`]: crate::Vt::draw_progress_bar
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct ProgressStyle {
+    /// Character used for the completed part of the bar.
+    pub filled: char,
+    /// Character used for the remaining part of the bar.
+    pub empty: char
+}
+
+impl Default for ProgressStyle {
+    fn default() -> ProgressStyle {
+        ProgressStyle {
+            filled: '█',
+            empty: '░'
+        }
+    }
+}
+
 /// An allocated virtual terminal.
+/// 
+/// A `Vt` borrows the [`Console`] it was created from.
+/// Use [`Console::new_vt_owned`] to get an [`OwnedVt`] that does not.
+/// 
+/// [`Console`]: crate::Console
+/// [`Console::new_vt_owned`]: crate::Console::new_vt_owned
+/// [`OwnedVt`]: crate::OwnedVt
 pub struct Vt<'a> {
-    console: &'a Console,
+    console: ConsoleRef<'a>,
     number: VtNumber,
-    file: File,
-    termios: Termios
+    file: ManuallyDrop<File>,
+    termios: Termios,
+    initial_termios: Termios,
+    previous_vt: Option<VtNumber>,
+    drop_policy: DropPolicy,
+    access: VtAccess,
+    read_buf: Vec<u8>,
+    tee: Option<Box<dyn Write + Send>>,
+    drain_on_flush: bool
 }
 
+/// A virtual terminal that shares ownership of its [`Console`] instead of borrowing it,
+/// as returned by [`Console::new_vt_owned`] and [`Console::open_vt_owned`].
+/// 
+/// [`Console`]: crate::Console
+/// [`Console::new_vt_owned`]: crate::Console::new_vt_owned
+/// [`Console::open_vt_owned`]: crate::Console::open_vt_owned
+pub type OwnedVt = Vt<'static>;
+
 impl<'a> Vt<'a> {
     
-    pub(crate) fn with_number(console: &'a Console, number: VtNumber) -> io::Result<Vt<'a>> {
+    pub(crate) fn with_number(console: ConsoleRef<'a>, number: VtNumber) -> Result<Vt<'a>> {
         
         // Open the device corresponding to the number of this vt
         let path = format!("/dev/tty{}", number);
@@ -95,118 +455,1296 @@ impl<'a> Vt<'a> {
         Vt::with_number_and_file(console, number, file)
     }
 
-    pub(crate) fn with_number_and_file(console: &'a Console, number: VtNumber, file: File) -> io::Result<Vt<'a>> {
+    pub(crate) fn with_number_and_file(console: ConsoleRef<'a>, number: VtNumber, file: File) -> Result<Vt<'a>> {
         
-        // Get the termios info for the current file
-        let mut termios = tcgetattr(file.as_raw_fd())
-                          .map_err(|e| io::Error::from_raw_os_error(e.as_errno().unwrap_or(nix::errno::Errno::UnknownErrno) as i32))?;
+        let mut vt = Vt::from_number_and_file(console, number, file)?;
+        vt.drop_policy = DropPolicy::Deallocate;
 
         // By default we turn off echo and signal generation.
         // We also disable Ctrl+D for EOF, since we will almost never want it.
-        termios.input_flags |= InputFlags::IGNBRK;
-        termios.local_flags &= !(LocalFlags::ECHO | LocalFlags::ISIG);
-        termios.control_chars[SpecialCharacterIndices::VEOF as usize] = 0;
+        vt.termios.input_flags |= InputFlags::IGNBRK;
+        vt.termios.local_flags &= !(LocalFlags::ECHO | LocalFlags::ISIG);
+        vt.termios.control_chars[SpecialCharacterIndices::VEOF as usize] = 0;
+
+        vt.update_termios()?;
+
+        Ok(vt)
+    }
+
+    /// Wraps the given file without altering its configuration.
+    /// The returned `Vt` uses `DropPolicy::KeepAllocated`, so it won't be disallocated on drop.
+    pub(crate) fn from_number_and_file(console: ConsoleRef<'a>, number: VtNumber, file: File) -> Result<Vt<'a>> {
+        
+        // Get the termios info for the current file
+        let termios = tcgetattr(file.as_raw_fd())?;
+
+        // Find out how the file was opened
+        let flags = unsafe { fcntl(file.as_raw_fd(), F_GETFL) };
+        if flags == -1 {
+            return Err(io::Error::last_os_error().into());
+        }
+        let access = match flags & O_ACCMODE {
+            O_RDONLY => VtAccess::ReadOnly,
+            O_WRONLY => VtAccess::WriteOnly,
+            _ => VtAccess::ReadWrite
+        };
+
+        // Remember where to go back to, for `DropPolicy::SwitchBack`
+        let previous_vt = console.current_vt_number().ok().filter(|n| *n != number);
+
+        Ok(Vt {
+            console,
+            number,
+            file: ManuallyDrop::new(file),
+            initial_termios: termios.clone(),
+            termios,
+            previous_vt,
+            drop_policy: DropPolicy::KeepAllocated,
+            access,
+            read_buf: Vec::new(),
+            tee: None,
+            drain_on_flush: false
+        })
+    }
+
+    fn update_termios(&self) -> Result<()> {
+        tcsetattr(
+            self.file.as_raw_fd(),
+            SetArg::TCSANOW,
+            &self.termios
+        )
+        .map_err(VtError::from)
+    }
+
+    /// Runs `f` with the given termios temporarily applied to the terminal,
+    /// restoring the current one afterwards, even if `f` fails.
+    fn with_termios<T, F>(&mut self, termios: Termios, f: F) -> Result<T>
+        where F: FnOnce(&mut Self) -> Result<T>
+    {
+        let saved = std::mem::replace(&mut self.termios, termios);
+        let res = self.update_termios().and_then(|_| f(self));
+        self.termios = saved;
+        let restored = self.update_termios();
+        let value = res?;
+        restored?;
+        Ok(value)
+    }
+
+    /// Runs `f` with signal generation (`ISIG`) disabled, so that keys like `Ctrl+C` are read as input.
+    pub(crate) fn without_signals<T, F>(&mut self, f: F) -> Result<T>
+        where F: FnOnce(&mut Self) -> Result<T>
+    {
+        let mut termios = self.termios.clone();
+        termios.local_flags &= !LocalFlags::ISIG;
+        self.with_termios(termios, f)
+    }
+
+    pub(crate) fn console(&self) -> &Console {
+        &self.console
+    }
+
+    pub(crate) fn file(&self) -> &File {
+        &self.file
+    }
+
+    /// Returns the number of this virtual terminal.
+    pub fn number(&self) -> VtNumber {
+        self.number
+    }
+
+    /// Consumes this `Vt`, returning a descriptor of the terminal device.
+    /// 
+    /// Pending output is transmitted first, and the terminal is left allocated and configured as it is,
+    /// regardless of the [`DropPolicy`], since it is now owned by the returned descriptor.
+    /// 
+    /// [`DropPolicy`]: crate::DropPolicy
+    pub fn into_owned_fd(mut self) -> Result<OwnedFd> {
+        let file = self.file.try_clone()?;
+        self.drop_policy = DropPolicy::KeepAllocated;
+        Ok(file.into())
+    }
+
+    /// Returns what will happen to this terminal when this `Vt` is dropped.
+    pub fn drop_policy(&self) -> DropPolicy {
+        self.drop_policy
+    }
+
+    /// Sets what will happen to this terminal when this `Vt` is dropped.
+    /// 
+    /// Returns `self` for chaining.
+    pub fn set_drop_policy(&mut self, policy: DropPolicy) -> &mut Self {
+        self.drop_policy = policy;
+        self
+    }
+
+    /// Returns whether this terminal can be read, written or both, depending on how its device was opened.
+    pub fn access_mode(&self) -> VtAccess {
+        self.access
+    }
+
+    fn check_writable(&self) -> Result<()> {
+        if self.access.is_writable() {
+            Ok(())
+        } else {
+            Err(VtError::new(io::ErrorKind::PermissionDenied, format!("tty{} has been opened read-only", self.number)))
+        }
+    }
+
+    /// Enables or disables non-blocking mode on the underlying file descriptor.
+    /// 
+    /// In non-blocking mode, reads and writes that would block fail with an error of kind [`WouldBlock`] instead,
+    /// which is needed to use the terminal in event loops like `mio` or `poll`.
+    /// 
+    /// [`WouldBlock`]: std::io::ErrorKind::WouldBlock
+    pub fn set_nonblocking(&self, nonblocking: bool) -> Result<()> {
+        let fd = self.file.as_raw_fd();
+        unsafe {
+            let flags = fcntl(fd, F_GETFL);
+            if flags == -1 {
+                return Err(io::Error::last_os_error().into());
+            }
+            let flags = if nonblocking { flags | O_NONBLOCK } else { flags & !O_NONBLOCK };
+            if fcntl(fd, F_SETFL, flags) == -1 {
+                return Err(io::Error::last_os_error().into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the name of the kernel driver backing this terminal.
+    /// 
+    /// The name is taken from the `/sys/class/tty/tty<N>/device/driver` symlink, which only exists
+    /// for terminals backed by a physical device. Otherwise, the driver registered for the major and minor numbers
+    /// of the terminal is looked up in `/proc/tty/drivers`: since the kernel registers the virtual terminal driver
+    /// without a name, `"vt"` is returned for it.
+    /// 
+    /// Returns an error of kind [`NotFound`] if no driver is registered for this terminal.
+    /// 
+    /// [`NotFound`]: std::io::ErrorKind::NotFound
+    pub fn driver_name(&self) -> Result<String> {
+        let path = format!("/sys/class/tty/tty{}/device/driver", self.number);
+        match std::fs::read_link(&path) {
+            Ok(target) => {
+                return target.file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .ok_or_else(|| VtError::new(io::ErrorKind::InvalidData, format!("Invalid driver link {}", target.display())));
+            },
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => {},
+            Err(e) => return Err(e.into())
+        }
+
+        let rdev = self.file.metadata()?.rdev();
+        let drivers = std::fs::read_to_string("/proc/tty/drivers")?;
+        find_tty_driver(&drivers, major(rdev), minor(rdev))
+            .ok_or_else(|| VtError::new(io::ErrorKind::NotFound, format!("No driver found for tty{}", self.number)))
+    }
+
+    /// Returns the size of the window of this terminal.
+    pub fn window_size(&self) -> Result<WindowSize> {
+        let ws = ffi::tiocgwinsz(self.file.as_raw_fd())?;
+        Ok(WindowSize {
+            rows: ws.ws_row,
+            cols: ws.ws_col,
+            pixel_width: ws.ws_xpixel,
+            pixel_height: ws.ws_ypixel
+        })
+    }
+
+    /// Returns the number of columns of this terminal.
+    /// This is just a shortcut for [`Vt::window_size`].
+    /// 
+    /// [`Vt::window_size`]: crate::Vt::window_size
+    pub fn columns(&self) -> Result<u16> {
+        self.window_size().map(|ws| ws.cols)
+    }
+
+    /// Returns the number of rows of this terminal.
+    /// This is just a shortcut for [`Vt::window_size`].
+    /// 
+    /// [`Vt::window_size`]: crate::Vt::window_size
+    pub fn rows(&self) -> Result<u16> {
+        self.window_size().map(|ws| ws.rows)
+    }
+
+    /// Returns the size of this terminal as `(rows, columns)`.
+    /// This is just a shortcut for [`Vt::window_size`].
+    /// 
+    /// [`Vt::window_size`]: crate::Vt::window_size
+    pub fn size(&self) -> Result<(u16, u16)> {
+        self.window_size().map(|ws| (ws.rows, ws.cols))
+    }
+
+    /// Resizes this terminal to the given number of rows and columns.
+    /// The kernel resizes the console accordingly, and sends `SIGWINCH` to the foreground process group.
+    /// 
+    /// Returns `self` for chaining.
+    pub fn set_size(&mut self, rows: u16, cols: u16) -> Result<&mut Self> {
+        let mut ws = ffi::tiocgwinsz(self.file.as_raw_fd())?;
+        ws.ws_row = rows;
+        ws.ws_col = cols;
+        ffi::tiocswinsz(self.file.as_raw_fd(), &ws)?;
+        Ok(self)
+    }
+
+    /// Returns how long this terminal has been idle, that is, the time elapsed since the last input.
+    /// 
+    /// Like `w` and `who`, this is computed from the access time of the `/dev/tty<N>` device,
+    /// which the kernel updates when the user types. For this reason the result depends on
+    /// the filesystem tracking access times (it won't if mounted with `noatime`), and is only approximate.
+    pub fn idle_time(&self) -> Result<Duration> {
+        let accessed = std::fs::metadata(format!("/dev/tty{}", self.number))?.accessed()?;
+        Ok(SystemTime::now().duration_since(accessed).unwrap_or_default())
+    }
+
+    /// Switches to this virtual terminal. This is just a shortcut for [`Console::switch_to`].
+    /// 
+    /// Returns `self` for chaining.
+    /// 
+    /// [`Console::switch_to`]: crate::Console::switch_to
+    pub fn switch(&self) -> Result<&Self> {
+        self.console.switch_to(self.number)?;
+        Ok(self)
+    }
+
+    /// Returns whether this terminal is the currently active one.
+    pub fn is_active(&self) -> Result<bool> {
+        Ok(self.console.current_vt_number()? == self.number)
+    }
+
+    /// Writes the given bytes only if this terminal is the currently active one,
+    /// returning whether the bytes have been written.
+    /// 
+    /// This is meant as an optimization to avoid rendering to a terminal that is not visible,
+    /// and not as a guarantee: the active terminal might change between the check and the write.
+    /// Use [`Vt::switch_and_write`] when the output must be visible.
+    /// 
+    /// [`Vt::switch_and_write`]: crate::Vt::switch_and_write
+    pub fn write_if_active(&mut self, bytes: &[u8]) -> Result<bool> {
+        if !self.is_active()? {
+            return Ok(false);
+        }
+        self.write_all(bytes)?;
+        Ok(true)
+    }
+
+    /// Switches to this virtual terminal and writes the given bytes,
+    /// guaranteeing that no other process switches away in between.
+    /// 
+    /// To achieve this, terminal switching is locked while writing the bytes:
+    /// note that the lock is global, so for that brief time switching is disabled for all the terminals,
+    /// and it is unconditionally released afterwards.
+    /// 
+    /// Returns `self` for chaining.
+    pub fn switch_and_write(&mut self, bytes: &[u8]) -> Result<&mut Self> {
+        
+        // The kernel refuses to activate a vt while switching is locked,
+        // so we switch first, then lock and check that nobody switched away in the meantime.
+        loop {
+            self.console.switch_to(self.number)?;
+            self.console.lock_switch(true)?;
+            let res = self.console.current_vt_number().and_then(|current| {
+                if current == self.number {
+                    self.write_all(bytes).map(|_| true).map_err(VtError::from)
+                } else {
+                    Ok(false)
+                }
+            });
+            self.console.lock_switch(false)?;
+            if res? {
+                break;
+            }
+        }
+
+        Ok(self)
+    }
+
+    /// Clears the terminal.
+    /// 
+    /// Returns `self` for chaining.
+    pub fn clear(&mut self) -> Result<&mut Self> {
+        write!(self, "\x1b[H\x1b[J")?;
+        Ok(self)
+    }
+
+    /// Clears the line the cursor is on, without moving the cursor.
+    /// 
+    /// Returns `self` for chaining.
+    pub fn clear_line(&mut self) -> Result<&mut Self> {
+        write!(self, "\x1b[2K")?;
+        Ok(self)
+    }
+
+    /// Clears the line the cursor is on from the cursor to the end, included.
+    /// 
+    /// Returns `self` for chaining.
+    pub fn clear_to_end_of_line(&mut self) -> Result<&mut Self> {
+        write!(self, "\x1b[K")?;
+        Ok(self)
+    }
+
+    /// Clears the screen from the cursor to the end, included, without moving the cursor.
+    /// 
+    /// Returns `self` for chaining.
+    pub fn clear_to_end_of_screen(&mut self) -> Result<&mut Self> {
+        write!(self, "\x1b[J")?;
+        Ok(self)
+    }
+
+    /// Clears the screen from the beginning to the cursor, included, without moving the cursor.
+    /// 
+    /// Returns `self` for chaining.
+    pub fn clear_from_start(&mut self) -> Result<&mut Self> {
+        write!(self, "\x1b[1J")?;
+        Ok(self)
+    }
+
+    /// Restricts scrolling to the rows between `top` and `bottom` included, 0-based,
+    /// so that the rows outside of the region (like a status bar) stay in place when the output scrolls.
+    /// The cursor is moved to the top left corner of the screen.
+    /// 
+    /// Returns an error of kind [`InvalidInput`] if `top` is not above `bottom`, or if `bottom` is outside of the screen.
+    /// 
+    /// Returns `self` for chaining.
+    /// 
+    /// [`InvalidInput`]: std::io::ErrorKind::InvalidInput
+    pub fn set_scroll_region(&mut self, top: u16, bottom: u16) -> Result<&mut Self> {
+        if top >= bottom || bottom >= self.rows()? {
+            return Err(VtError::new(io::ErrorKind::InvalidInput, format!("Invalid scroll region: {}-{}", top, bottom)));
+        }
+        write!(self, "\x1b[{};{}r", u32::from(top) + 1, u32::from(bottom) + 1)?;
+        Ok(self)
+    }
+
+    /// Makes the whole screen scroll again, after a call to [`Vt::set_scroll_region`].
+    /// The cursor is moved to the top left corner of the screen.
+    /// 
+    /// Returns `self` for chaining.
+    /// 
+    /// [`Vt::set_scroll_region`]: crate::Vt::set_scroll_region
+    pub fn reset_scroll_region(&mut self) -> Result<&mut Self> {
+        write!(self, "\x1b[r")?;
+        Ok(self)
+    }
+
+    /// Scrolls the contents of the scroll region up by the given number of lines,
+    /// adding blank lines at the bottom. The cursor does not move.
+    /// 
+    /// Unlike [`Vt::scroll`], this moves the text on the screen instead of the view over the scrollback.
+    /// 
+    /// Returns `self` for chaining.
+    /// 
+    /// [`Vt::scroll`]: crate::Vt::scroll
+    pub fn scroll_up(&mut self, lines: u16) -> Result<&mut Self> {
+        self.scroll_region_lines(lines, 'M')
+    }
+
+    /// Scrolls the contents of the scroll region down by the given number of lines,
+    /// adding blank lines at the top. The cursor does not move.
+    /// 
+    /// Returns `self` for chaining.
+    pub fn scroll_down(&mut self, lines: u16) -> Result<&mut Self> {
+        self.scroll_region_lines(lines, 'L')
+    }
+
+    fn scroll_region_lines(&mut self, lines: u16, op: char) -> Result<&mut Self> {
+        if lines == 0 {
+            return Ok(self);
+        }
+        // The console has no scroll commands, so delete or insert lines at the top of the region:
+        // origin mode moves the cursor there without having to know where the region starts.
+        write!(self, "\x1b7\x1b[?6h\x1b[{}{}\x1b[?6l\x1b8", lines, op)?;
+        Ok(self)
+    }
+
+    /// Shows or hides the cursor.
+    /// 
+    /// Returns `self` for chaining.
+    pub fn set_cursor_visible(&mut self, visible: bool) -> Result<&mut Self> {
+        write!(self, "\x1b[?25{}", if visible { 'h' } else { 'l' })?;
+        Ok(self)
+    }
+
+    /// Changes the shape of the cursor, using the Linux console specific `ESC [ ? n c` escape sequence.
+    /// The shape is kept until it is changed again, even after the program exits,
+    /// so remember to restore [`CursorShape::Default`].
+    /// 
+    /// Returns `self` for chaining.
+    /// 
+    /// [`CursorShape::Default`]: crate::CursorShape::Default
+    pub fn set_cursor_shape(&mut self, shape: CursorShape) -> Result<&mut Self> {
+        let size = match shape {
+            CursorShape::Default => 0,
+            CursorShape::Invisible => 1,
+            CursorShape::Underline => 2,
+            CursorShape::LowerThird => 3,
+            CursorShape::LowerHalf => 4,
+            CursorShape::TwoThirds => 5,
+            CursorShape::Block => 6,
+            CursorShape::Software { toggle, set } => {
+                // Hide the hardware cursor (1) and enable the software one (16)
+                write!(self, "\x1b[?17;{};{}c", toggle, set)?;
+                return Ok(self);
+            }
+        };
+        write!(self, "\x1b[?{}c", size)?;
+        Ok(self)
+    }
+
+    /// Draws a progress bar `width` cells wide, filled proportionally to `fraction`,
+    /// which is clamped between `0.0` and `1.0`. If no style is given, [`ProgressStyle::default`] is used.
+    /// 
+    /// The bar is drawn at the beginning of the current line without moving to the next one,
+    /// so that repeated calls update the same bar in place.
+    /// 
+    /// Returns `self` for chaining.
+    /// 
+    /// [`ProgressStyle::default`]: crate::ProgressStyle::default
+    pub fn draw_progress_bar(&mut self, fraction: f32, width: u16, style: Option<&ProgressStyle>) -> Result<&mut Self> {
+        let style = style.copied().unwrap_or_default();
+        let fraction = if fraction.is_nan() { 0.0 } else { fraction.clamp(0.0, 1.0) };
+        let filled = (fraction * width as f32).round() as usize;
+
+        let mut bar = String::from("\r");
+        bar.extend(std::iter::repeat_n(style.filled, filled));
+        bar.extend(std::iter::repeat_n(style.empty, width as usize - filled));
+        self.write_all(bar.as_bytes())?;
+
+        Ok(self)
+    }
+
+    /// Draws an image made of `width` x `height` RGB pixels, in row-major order, starting from the cursor position.
+    /// 
+    /// Each character cell represents two vertically adjacent pixels, by drawing the upper half block
+    /// character (`▀`) with the top pixel as foreground color and the bottom one as background color.
+    /// Colors are approximated to the 256-color palette. If `height` is odd, the missing bottom row is black.
+    /// 
+    /// Every row of cells starts in the column the cursor was in, and the screen scrolls if the image
+    /// reaches the bottom. The cursor is left below the image, in the starting column.
+    /// Rows are positioned by saving and restoring the cursor (`ESC 7` and `ESC 8`), so any previously saved position is lost.
+    /// 
+    /// Returns an error of kind [`InvalidInput`] if the number of pixels does not match the size of the image.
+    /// 
+    /// Returns `self` for chaining.
+    /// 
+    /// [`InvalidInput`]: std::io::ErrorKind::InvalidInput
+    pub fn draw_image(&mut self, pixels: &[(u8, u8, u8)], width: u16, height: u16) -> Result<&mut Self> {
+        let (width, height) = (width as usize, height as usize);
+        if pixels.len() != width * height {
+            return Err(VtError::new(io::ErrorKind::InvalidInput, "The number of pixels does not match the size of the image"));
+        }
+
+        use std::fmt::Write as _;
+
+        let mut out = String::new();
+        for y in (0..height).step_by(2) {
+            // Go back to the start of the row and move down a line, scrolling if needed, without changing the column
+            out.push_str("\x1b7");
+            for x in 0..width {
+                let top = pixels[y * width + x];
+                let bottom = if y + 1 < height { pixels[(y + 1) * width + x] } else { (0, 0, 0) };
+                let _ = write!(out, "\x1b[38;5;{};48;5;{}m▀", rgb_to_ansi256(top), rgb_to_ansi256(bottom));
+            }
+            out.push_str("\x1b8\x1bD");
+        }
+        out.push_str("\x1b[0m");
+        self.write_all(out.as_bytes())?;
+
+        Ok(self)
+    }
+
+    /// Writes the given text wrapping it at `width` columns.
+    /// 
+    /// Lines are broken on spaces where possible, while words longer than `width`
+    /// are split across multiple lines. Newlines already present in the text are preserved.
+    /// 
+    /// Returns `self` for chaining.
+    pub fn write_wrapped(&mut self, text: &str, width: u16) -> Result<&mut Self> {
+        if width == 0 {
+            return Err(VtError::new(io::ErrorKind::InvalidInput, "Wrapping width must be greater than zero"));
+        }
+        let width = width as usize;
+
+        let mut lines: Vec<String> = Vec::new();
+        for paragraph in text.split('\n') {
+            let mut line = String::new();
+            let mut line_len = 0;
+            for word in paragraph.split(' ').filter(|w| !w.is_empty()) {
+                let mut word: Vec<char> = word.chars().collect();
+
+                // Move to the next line if the word does not fit in the current one
+                if line_len > 0 && line_len + 1 + word.len() > width {
+                    lines.push(std::mem::take(&mut line));
+                    line_len = 0;
+                }
+
+                // Hard-break words longer than a whole line
+                while word.len() > width {
+                    let rest = word.split_off(width);
+                    lines.push(word.into_iter().collect());
+                    word = rest;
+                }
+
+                if line_len > 0 {
+                    line.push(' ');
+                    line_len += 1;
+                }
+                line.extend(word.iter());
+                line_len += word.len();
+            }
+            lines.push(line);
+        }
+
+        self.write_all(lines.join("\n").as_bytes())?;
+        Ok(self)
+    }
+
+    /// Sets the blank timer for this terminal, rounded up to whole minutes.
+    /// A zero duration disables the timer.
+    /// 
+    /// Returns an error of kind [`InvalidInput`] if the duration is longer than 60 minutes.
+    /// 
+    /// Returns `self` for chaining.
+    /// 
+    /// [`InvalidInput`]: std::io::ErrorKind::InvalidInput
+    pub fn set_blank_timer(&mut self, timer: Duration) -> Result<&mut Self> {
+        let minutes = units::minutes_from_duration(timer, 60)?;
+        write!(self, "\x1b[9;{}]", minutes)?;
+        Ok(self)
+    }
+
+    /// Sets the VESA powerdown timer for this terminal, rounded up to whole minutes.
+    /// A zero duration disables the timer.
+    /// 
+    /// Returns an error of kind [`InvalidInput`] if the duration is longer than 60 minutes.
+    /// 
+    /// Returns `self` for chaining.
+    /// 
+    /// [`InvalidInput`]: std::io::ErrorKind::InvalidInput
+    pub fn set_powerdown_timer(&mut self, timer: Duration) -> Result<&mut Self> {
+        let minutes = units::minutes_from_duration(timer, 60)?;
+        write!(self, "\x1b[14;{}]", minutes)?;
+        Ok(self)
+    }
+
+    /// Blanks or unlanks the terminal.
+    /// 
+    /// Returns `self` for chaining.
+    pub fn blank(&mut self, blank: bool) -> Result<&mut Self> {
+        
+        // If the console blanking timer is disabled, the ioctl below will fail,
+        // so we need to enable it just for the time needed for the ioctl to work.
+        let needs_timer_reset = if blank && self.console.blank_timer()? == Duration::from_secs(0) {
+            self.set_blank_timer(Duration::from_secs(60))?;
+            true
+        } else {
+            false
+        };
+
+        let mut arg = if blank { ffi::TIOCL_BLANKSCREEN } else { ffi::TIOCL_UNBLANKSCREEN };
+        ffi::tioclinux(self.file.as_raw_fd(), &mut arg)?;
+
+        // Disable the blank timer if originally it was disabled
+        if needs_timer_reset {
+            self.set_blank_timer(Duration::from_secs(0))?;
+        }
+
+        Ok(self)
+    }
+
+    /// Returns the display mode of this terminal.
+    pub fn kd_mode(&self) -> Result<KdMode> {
+        match ffi::kdgetmode(self.file.as_raw_fd())? {
+            ffi::KD_GRAPHICS => Ok(KdMode::Graphics),
+            _ => Ok(KdMode::Text)
+        }
+    }
+
+    /// Sets the display mode of this terminal.
+    /// 
+    /// Applications drawing directly to the screen (for example via DRM or fbdev) must set
+    /// [`KdMode::Graphics`], otherwise the kernel keeps drawing the text console over their frames.
+    /// Consider using [`Vt::graphics_mode`] to make sure that text mode is restored.
+    /// 
+    /// Returns `self` for chaining.
+    /// 
+    /// [`KdMode::Graphics`]: crate::KdMode::Graphics
+    /// [`Vt::graphics_mode`]: crate::Vt::graphics_mode
+    pub fn set_kd_mode(&mut self, mode: KdMode) -> Result<&mut Self> {
+        let mode = match mode {
+            KdMode::Text => ffi::KD_TEXT,
+            KdMode::Graphics => ffi::KD_GRAPHICS
+        };
+        ffi::kdsetmode(self.file.as_raw_fd(), mode)?;
+        Ok(self)
+    }
+
+    /// Puts this terminal in graphics mode, returning a guard that restores text mode when dropped.
+    /// The guard dereferences to the terminal itself.
+    pub fn graphics_mode(&mut self) -> Result<GraphicsModeGuard<'_, 'a>> {
+        self.set_kd_mode(KdMode::Graphics)?;
+        Ok(GraphicsModeGuard { vt: self })
+    }
+
+    /// Returns the keyboard mode of this terminal.
+    pub fn keyboard_mode(&self) -> Result<KeyboardMode> {
+        match ffi::kdgkbmode(self.file.as_raw_fd())? {
+            ffi::K_RAW => Ok(KeyboardMode::Raw),
+            ffi::K_XLATE => Ok(KeyboardMode::Xlate),
+            ffi::K_MEDIUMRAW => Ok(KeyboardMode::MediumRaw),
+            ffi::K_UNICODE => Ok(KeyboardMode::Unicode),
+            ffi::K_OFF => Ok(KeyboardMode::Off),
+            mode => Err(VtError::new(io::ErrorKind::InvalidData, format!("Unknown keyboard mode: {}", mode)))
+        }
+    }
+
+    /// Sets the keyboard mode of this terminal.
+    /// 
+    /// Applications reading input directly from the input devices (for example via evdev)
+    /// should use [`KeyboardMode::Off`] to prevent keystrokes from reaching the terminal.
+    /// Consider using [`Vt::keyboard_mode_guard`] to make sure that the previous mode is restored.
+    /// 
+    /// Returns `self` for chaining.
+    /// 
+    /// [`KeyboardMode::Off`]: crate::KeyboardMode::Off
+    /// [`Vt::keyboard_mode_guard`]: crate::Vt::keyboard_mode_guard
+    pub fn set_keyboard_mode(&mut self, mode: KeyboardMode) -> Result<&mut Self> {
+        let mode = match mode {
+            KeyboardMode::Raw => ffi::K_RAW,
+            KeyboardMode::MediumRaw => ffi::K_MEDIUMRAW,
+            KeyboardMode::Xlate => ffi::K_XLATE,
+            KeyboardMode::Unicode => ffi::K_UNICODE,
+            KeyboardMode::Off => ffi::K_OFF
+        };
+        ffi::kdskbmode(self.file.as_raw_fd(), mode)?;
+        Ok(self)
+    }
+
+    /// Sets the keyboard mode of this terminal, returning a guard that restores the previous mode when dropped.
+    /// The guard dereferences to the terminal itself.
+    pub fn keyboard_mode_guard(&mut self, mode: KeyboardMode) -> Result<KeyboardModeGuard<'_, 'a>> {
+        let previous = self.keyboard_mode()?;
+        self.set_keyboard_mode(mode)?;
+        Ok(KeyboardModeGuard { vt: self, previous })
+    }
+
+    /// Stops keyboard input from reaching this terminal until the returned guard is dropped,
+    /// restoring the previous keyboard mode. The guard dereferences to the terminal itself.
+    /// 
+    /// This is meant for applications reading the keyboard directly from the input devices, like Wayland compositors
+    /// and games, which would otherwise leak keystrokes to the console. The keyboard is put in [`KeyboardMode::Off`];
+    /// kernels older than 2.6.39 do not support it, so [`KeyboardMode::Raw`] is used instead: in this case
+    /// the terminal still receives scancodes, which should be discarded with [`Vt::flush_buffers`].
+    /// 
+    /// [`KeyboardMode::Off`]: crate::KeyboardMode::Off
+    /// [`KeyboardMode::Raw`]: crate::KeyboardMode::Raw
+    /// [`Vt::flush_buffers`]: crate::Vt::flush_buffers
+    pub fn disable_console_input(&mut self) -> Result<KeyboardModeGuard<'_, 'a>> {
+        let previous = self.keyboard_mode()?;
+        match self.set_keyboard_mode(KeyboardMode::Off) {
+            Err(VtError::Ioctl { errno: EINVAL, .. }) => { self.set_keyboard_mode(KeyboardMode::Raw)?; },
+            res => { res?; }
+        }
+        Ok(KeyboardModeGuard { vt: self, previous })
+    }
+
+    /// Returns the keyboard LEDs currently lit.
+    pub fn leds(&self) -> Result<VtLeds> {
+        let leds = ffi::kdgetled(self.file.as_raw_fd())?;
+        Ok(VtLeds::from_bits_truncate(leds as u8))
+    }
+
+    /// Lights the given keyboard LEDs, turning off all the others.
+    /// 
+    /// From now on the LEDs no longer reflect the state of the lock keys,
+    /// until [`Vt::reset_leds`] is called.
+    /// 
+    /// Returns `self` for chaining.
+    /// 
+    /// [`Vt::reset_leds`]: crate::Vt::reset_leds
+    pub fn set_leds(&mut self, leds: VtLeds) -> Result<&mut Self> {
+        ffi::kdsetled(self.file.as_raw_fd(), leds.bits().into())?;
+        Ok(self)
+    }
+
+    /// Makes the keyboard LEDs reflect the state of the lock keys again, after a call to [`Vt::set_leds`].
+    /// 
+    /// Returns `self` for chaining.
+    /// 
+    /// [`Vt::set_leds`]: crate::Vt::set_leds
+    pub fn reset_leds(&mut self) -> Result<&mut Self> {
+        ffi::kdsetled(self.file.as_raw_fd(), 0xFF)?;
+        Ok(self)
+    }
+
+    /// Registers the calling process to receive `signal` when the `KeyboardSignal` key is pressed
+    /// (usually bound to `Alt+Up`), like `init` does to spawn a new console on request.
+    /// 
+    /// There is only one such process for the whole system, so this replaces any previous registration.
+    /// `SIGKILL` is not accepted, and changing the registration requires owning this terminal
+    /// or the `CAP_SYS_TTY_CONFIG` capability.
+    /// 
+    /// Returns `self` for chaining.
+    pub fn accept_keyboard_signal(&mut self, signal: Signal) -> Result<&mut Self> {
+        ffi::kdsigaccept(self.file.as_raw_fd(), signal as c_ulong)?;
+        Ok(self)
+    }
+
+    /// Returns the lock keys currently active on this terminal.
+    /// 
+    /// Unlike [`Vt::leds`], this is the state actually used to translate keys, even when the LEDs
+    /// have been changed with [`Vt::set_leds`]: for example, it tells if typed letters are going to be uppercase.
+    /// 
+    /// [`Vt::leds`]: crate::Vt::leds
+    /// [`Vt::set_leds`]: crate::Vt::set_leds
+    pub fn locks(&self) -> Result<KeyboardLocks> {
+        let flags = ffi::kdgkbled(self.file.as_raw_fd())?;
+        Ok(KeyboardLocks::from_bits_truncate(flags as u8))
+    }
+
+    /// Changes the lock keys active on this terminal, as if the corresponding keys had been pressed.
+    /// The LEDs are updated accordingly, unless they have been changed with [`Vt::set_leds`].
+    /// 
+    /// Returns `self` for chaining.
+    /// 
+    /// [`Vt::set_leds`]: crate::Vt::set_leds
+    pub fn set_locks(&mut self, locks: KeyboardLocks) -> Result<&mut Self> {
+        // The upper nibble holds the locks restored on reset, which are kept unchanged
+        let current = ffi::kdgkbled(self.file.as_raw_fd())? as u8;
+        ffi::kdskbled(self.file.as_raw_fd(), ((current & 0x70) | locks.bits()).into())?;
+        Ok(self)
+    }
+
+    /// Returns the modifier keys currently held down on the keyboard.
+    /// 
+    /// The shift state is shared by all the terminals, since they all read from the same keyboard.
+    pub fn shift_state(&self) -> Result<ShiftState> {
+        let mut arg = ffi::TIOCL_GETSHIFTSTATE;
+        ffi::tioclinux_bytes(self.file.as_raw_fd(), &mut arg)?;
+        Ok(ShiftState::from_bits_truncate(arg as u8))
+    }
+
+    /// Produces the standard console bell sound: a 750 Hz tone for 125 ms.
+    /// 
+    /// Returns `self` for chaining.
+    pub fn beep(&mut self) -> Result<&mut Self> {
+        self.tone(750, Duration::from_millis(125))
+    }
+
+    /// Plays a tone with the given frequency for the given duration on the PC speaker.
+    /// This returns immediately, without waiting for the tone to end.
+    /// 
+    /// Returns an error of kind [`InvalidInput`] if the frequency is not between 19 Hz and 1193182 Hz,
+    /// or if the duration is longer than 65535 milliseconds.
+    /// 
+    /// Returns `self` for chaining.
+    /// 
+    /// [`InvalidInput`]: std::io::ErrorKind::InvalidInput
+    pub fn tone(&mut self, frequency_hz: u32, duration: Duration) -> Result<&mut Self> {
+        let period = tone_period(frequency_hz)?;
+        let millis = duration.as_millis();
+        if millis > u16::MAX as u128 {
+            return Err(VtError::new(io::ErrorKind::InvalidInput, "Tone duration too long"));
+        }
+        ffi::kdmktone(self.file.as_raw_fd(), (millis as c_ulong) << 16 | period)?;
+        Ok(self)
+    }
+
+    /// Starts playing a tone with the given frequency on the PC speaker, until [`Vt::stop_sound`] is called.
+    /// 
+    /// Returns an error of kind [`InvalidInput`] if the frequency is not between 19 Hz and 1193182 Hz.
+    /// 
+    /// Returns `self` for chaining.
+    /// 
+    /// [`Vt::stop_sound`]: crate::Vt::stop_sound
+    /// [`InvalidInput`]: std::io::ErrorKind::InvalidInput
+    pub fn start_sound(&mut self, frequency_hz: u32) -> Result<&mut Self> {
+        let period = tone_period(frequency_hz)?;
+        ffi::kiocsound(self.file.as_raw_fd(), period)?;
+        Ok(self)
+    }
+
+    /// Stops the tone started with [`Vt::start_sound`].
+    /// 
+    /// Returns `self` for chaining.
+    /// 
+    /// [`Vt::start_sound`]: crate::Vt::start_sound
+    pub fn stop_sound(&mut self) -> Result<&mut Self> {
+        ffi::kiocsound(self.file.as_raw_fd(), 0)?;
+        Ok(self)
+    }
+
+    /// Returns how switches from and to this terminal are handled.
+    pub fn switch_mode(&self) -> Result<SwitchMode> {
+        let mode = ffi::vt_getmode(self.file.as_raw_fd())?;
+        if mode.mode != ffi::VT_PROCESS {
+            return Ok(SwitchMode::Auto);
+        }
+        let signal = |n: c_short| Signal::from_c_int(n.into())
+            .map_err(|_| VtError::new(io::ErrorKind::InvalidData, format!("Invalid switch signal: {}", n)));
+        Ok(SwitchMode::Process {
+            release: signal(mode.relsig)?,
+            acquire: signal(mode.acqsig)?
+        })
+    }
+
+    /// Changes how switches from and to this terminal are handled.
+    /// 
+    /// In [`SwitchMode::Process`] mode, the signals are delivered to the calling process,
+    /// which must handle them to acknowledge the switches, otherwise switching gets stuck.
+    /// 
+    /// Returns `self` for chaining.
+    /// 
+    /// [`SwitchMode::Process`]: crate::SwitchMode::Process
+    pub fn set_switch_mode(&mut self, mode: SwitchMode) -> Result<&mut Self> {
+        let mode = match mode {
+            SwitchMode::Auto => ffi::VtMode { mode: ffi::VT_AUTO, waitv: 0, relsig: 0, acqsig: 0, frsig: 0 },
+            SwitchMode::Process { release, acquire } => ffi::VtMode {
+                mode: ffi::VT_PROCESS,
+                waitv: 0,
+                relsig: release as c_short,
+                acqsig: acquire as c_short,
+                frsig: 0
+            }
+        };
+        ffi::vt_setmode(self.file.as_raw_fd(), &mode)?;
+        Ok(self)
+    }
+
+    /// Answers a release request received in [`SwitchMode::Process`] mode,
+    /// allowing or refusing the switch away from this terminal.
+    /// 
+    /// Returns `self` for chaining.
+    /// 
+    /// [`SwitchMode::Process`]: crate::SwitchMode::Process
+    pub fn release_display(&mut self, allow: bool) -> Result<&mut Self> {
+        ffi::vt_reldisp(self.file.as_raw_fd(), if allow { 1 } else { 0 })?;
+        Ok(self)
+    }
+
+    /// Acknowledges that this terminal has been acquired again in [`SwitchMode::Process`] mode.
+    /// 
+    /// Returns `self` for chaining.
+    /// 
+    /// [`SwitchMode::Process`]: crate::SwitchMode::Process
+    pub fn acknowledge_acquire(&mut self) -> Result<&mut Self> {
+        ffi::vt_reldisp(self.file.as_raw_fd(), ffi::VT_ACKACQ)?;
+        Ok(self)
+    }
+
+    /// Enables or disables the echo of the characters typed by the user.
+    /// 
+    /// Returns `self` for chaining.
+    pub fn set_echo(&mut self, echo: bool) -> Result<&mut Self> {
+        if echo {
+            self.termios.local_flags |= LocalFlags::ECHO;
+        } else {
+            self.termios.local_flags &= !LocalFlags::ECHO;
+        }
+        self.update_termios()?;
+
+        Ok(self)
+    }
+
+    /// Returns a value indicating whether this terminal has echo enabled or not.
+    pub fn is_echo_enabled(&self) -> bool {
+        self.termios.local_flags.contains(LocalFlags::ECHO)
+    }
+
+    /// Returns the processing applied by this terminal to the output.
+    pub fn output_processing(&self) -> OutputProcessing {
+        let flags = self.termios.output_flags;
+        let mut processing = OutputProcessing::empty();
+        processing.set(OutputProcessing::ENABLED, flags.contains(OutputFlags::OPOST));
+        processing.set(OutputProcessing::NL_TO_CRNL, flags.contains(OutputFlags::ONLCR));
+        processing.set(OutputProcessing::CR_TO_NL, flags.contains(OutputFlags::OCRNL));
+        processing
+    }
+
+    /// Sets the processing applied by this terminal to the output.
+    /// 
+    /// Full-screen applications usually want to disable processing altogether, to be in full control
+    /// of the cursor, while simple loggers want `\n` to be translated to `\r\n`.
+    /// 
+    /// Returns `self` for chaining.
+    pub fn set_output_processing(&mut self, processing: OutputProcessing) -> Result<&mut Self> {
+        let flags = &mut self.termios.output_flags;
+        flags.set(OutputFlags::OPOST, processing.contains(OutputProcessing::ENABLED));
+        flags.set(OutputFlags::ONLCR, processing.contains(OutputProcessing::NL_TO_CRNL));
+        flags.set(OutputFlags::OCRNL, processing.contains(OutputProcessing::CR_TO_NL));
+        self.update_termios()?;
+
+        Ok(self)
+    }
+
+    /// Enables or disables UTF-8 input. When enabled, the keyboard produces UTF-8 encoded characters
+    /// ([`KeyboardMode::Unicode`]) and the line editing of canonical mode handles multi-byte characters (`IUTF8`).
+    /// When disabled, the keyboard goes back to the 8-bit charset ([`KeyboardMode::Xlate`]).
+    /// 
+    /// Returns `self` for chaining.
+    /// 
+    /// [`KeyboardMode::Unicode`]: crate::KeyboardMode::Unicode
+    /// [`KeyboardMode::Xlate`]: crate::KeyboardMode::Xlate
+    pub fn set_utf8(&mut self, utf8: bool) -> Result<&mut Self> {
+        self.set_keyboard_mode(if utf8 { KeyboardMode::Unicode } else { KeyboardMode::Xlate })?;
+        self.termios.input_flags.set(InputFlags::IUTF8, utf8);
+        self.update_termios()?;
+
+        Ok(self)
+    }
+
+    /// Enables or disables canonical mode. In canonical mode input is delivered one line at a time,
+    /// and can be edited before pressing enter. In non-canonical mode input is available as soon as it is typed,
+    /// subject to [`Vt::set_min_bytes`] and [`Vt::set_read_timeout`].
+    /// 
+    /// Returns `self` for chaining.
+    /// 
+    /// [`Vt::set_min_bytes`]: crate::Vt::set_min_bytes
+    /// [`Vt::set_read_timeout`]: crate::Vt::set_read_timeout
+    pub fn set_canonical(&mut self, canonical: bool) -> Result<&mut Self> {
+        if canonical {
+            self.termios.local_flags |= LocalFlags::ICANON;
+        } else {
+            self.termios.local_flags &= !LocalFlags::ICANON;
+        }
+        self.update_termios()?;
+
+        Ok(self)
+    }
+
+    /// Returns a value indicating whether this terminal is in canonical mode or not.
+    pub fn is_canonical(&self) -> bool {
+        self.termios.local_flags.contains(LocalFlags::ICANON)
+    }
+
+    /// Reads a single keypress from the terminal, echoing it if `echo` is `true`.
+    /// 
+    /// The terminal is temporarily switched to non-canonical mode, so that the key is available
+    /// as soon as it is pressed, without waiting for a newline. The previous mode is always restored
+    /// before returning, even if the read fails.
+    /// 
+    /// Keys producing multi-byte sequences (like arrows) should be read with [`Vt::read_key_sequence`].
+    /// 
+    /// [`Vt::read_key_sequence`]: crate::Vt::read_key_sequence
+    pub fn read_key(&mut self, echo: bool) -> Result<u8> {
+        let mut termios = self.termios.clone();
+        termios.local_flags &= !LocalFlags::ICANON;
+        if echo {
+            termios.local_flags |= LocalFlags::ECHO;
+        } else {
+            termios.local_flags &= !LocalFlags::ECHO;
+        }
+        termios.control_chars[SpecialCharacterIndices::VMIN as usize] = 1;
+        termios.control_chars[SpecialCharacterIndices::VTIME as usize] = 0;
+
+        self.with_termios(termios, |vt| {
+            let mut buf = [0u8; 1];
+            vt.read_exact(&mut buf)?;
+            Ok(buf[0])
+        })
+    }
+
+    /// Reads all the bytes generated by a single keypress, without echoing them.
+    /// 
+    /// Unlike [`Vt::read_key`], this also returns complete escape sequences,
+    /// like the 3 bytes sent by the arrow keys. The previous terminal mode is always restored
+    /// before returning, even if the read fails.
+    /// 
+    /// [`Vt::read_key`]: crate::Vt::read_key
+    pub fn read_key_sequence(&mut self) -> Result<Vec<u8>> {
+        let mut termios = self.termios.clone();
+        termios.local_flags &= !(LocalFlags::ICANON | LocalFlags::ECHO);
+        termios.control_chars[SpecialCharacterIndices::VMIN as usize] = 1;
+        termios.control_chars[SpecialCharacterIndices::VTIME as usize] = 0;
+
+        self.with_termios(termios, |vt| {
+            let mut buf = [0u8; 32];
+            let n = vt.read(&mut buf)?;
+            let mut seq = buf[..n].to_vec();
+
+            // The kernel emits escape sequences all at once, but in case they get split,
+            // wait a little for the rest of the sequence to arrive.
+            if seq == [0x1b] {
+                let mut termios = vt.termios.clone();
+                termios.control_chars[SpecialCharacterIndices::VMIN as usize] = 0;
+                termios.control_chars[SpecialCharacterIndices::VTIME as usize] =
+                    units::decisecs_from_duration(ESCAPE_SEQUENCE_TIMEOUT, u8::MAX as u32)? as u8;
+                let n = vt.with_termios(termios, |vt| Ok(vt.read(&mut buf)?))?;
+                seq.extend_from_slice(&buf[..n]);
+            }
+
+            Ok(seq)
+        })
+    }
+
+    /// Reads and decodes a single keypress, without echoing it.
+    /// 
+    /// Escape sequences are decoded into structured keys, like arrows and function keys,
+    /// and an `ESC` prefix is reported as the `Alt` modifier. A lone `ESC` not followed by anything
+    /// within a short time is reported as [`Key::Escape`]. The previous terminal mode is always restored
+    /// before returning, even if the read fails. Any bytes read past the event are kept
+    /// and returned by subsequent reads.
+    /// 
+    /// To decode the input read in other ways, like with `AsyncVt`, use a [`KeyDecoder`].
+    /// 
+    /// [`Key::Escape`]: crate::Key::Escape
+    /// [`KeyDecoder`]: crate::KeyDecoder
+    pub fn read_key_event(&mut self) -> Result<KeyEvent> {
+        let mut termios = self.termios.clone();
+        termios.local_flags &= !(LocalFlags::ICANON | LocalFlags::ECHO);
+        termios.control_chars[SpecialCharacterIndices::VMIN as usize] = 1;
+        termios.control_chars[SpecialCharacterIndices::VTIME as usize] = 0;
 
-        let vt = Vt {
-            console,
-            number,
-            file,
-            termios
-        };
+        self.with_termios(termios, |vt| {
+            loop {
+                if let Some((event, n)) = input::decode(&vt.read_buf, false) {
+                    vt.read_buf.drain(..n);
+                    return Ok(event);
+                }
 
-        vt.update_termios()?;
+                if vt.read_buf.is_empty() {
+                    if !vt.fill_read_buf()? {
+                        return Err(VtError::new(io::ErrorKind::UnexpectedEof, "End of input while reading a key"));
+                    }
+                    continue;
+                }
 
-        Ok(vt)
+                // Incomplete sequence: wait a little for the rest, then decode what we have
+                let mut termios = vt.termios.clone();
+                termios.control_chars[SpecialCharacterIndices::VMIN as usize] = 0;
+                termios.control_chars[SpecialCharacterIndices::VTIME as usize] =
+                    units::decisecs_from_duration(ESCAPE_SEQUENCE_TIMEOUT, u8::MAX as u32)? as u8;
+                if !vt.with_termios(termios, |vt| vt.fill_read_buf())? {
+                    if let Some((event, n)) = input::decode(&vt.read_buf, true) {
+                        vt.read_buf.drain(..n);
+                        return Ok(event);
+                    }
+                }
+            }
+        })
     }
 
-    fn update_termios(&self) -> io::Result<()> {
-        tcsetattr(
-            self.file.as_raw_fd(),
-            SetArg::TCSANOW,
-            &self.termios
-        )
-        .map_err(|e| io::Error::from_raw_os_error(e.as_errno().unwrap_or(nix::errno::Errno::UnknownErrno) as i32))
-    }
+    /// Reads a single key press or release from a terminal in medium raw keyboard mode
+    /// (see [`Vt::set_keyboard_mode`]).
+    /// 
+    /// The terminal is temporarily put in raw mode, so that keycodes are not altered by input processing.
+    /// The previous terminal mode is always restored before returning, even if the read fails.
+    /// Any bytes read past the event are kept and returned by subsequent reads.
+    /// 
+    /// To decode the input read in other ways, like with `AsyncVt`, use a [`MediumRawDecoder`].
+    /// 
+    /// [`Vt::set_keyboard_mode`]: crate::Vt::set_keyboard_mode
+    /// [`MediumRawDecoder`]: crate::MediumRawDecoder
+    pub fn read_raw_key_event(&mut self) -> Result<RawKeyEvent> {
+        let mut termios = self.termios.clone();
+        cfmakeraw(&mut termios);
+        termios.control_chars[SpecialCharacterIndices::VMIN as usize] = 1;
+        termios.control_chars[SpecialCharacterIndices::VTIME as usize] = 0;
 
-    /// Returns the number of this virtual terminal.
-    pub fn number(&self) -> VtNumber {
-        self.number
+        self.with_termios(termios, |vt| {
+            loop {
+                if let Some((event, n)) = input::decode_mediumraw(&vt.read_buf) {
+                    vt.read_buf.drain(..n);
+                    return Ok(event);
+                }
+                if !vt.fill_read_buf()? {
+                    vt.read_buf.clear();
+                    return Err(VtError::new(io::ErrorKind::UnexpectedEof, "End of input while reading a key"));
+                }
+            }
+        })
     }
 
-    /// Switches to this virtual terminal. This is just a shortcut for [`Console::switch_to`].
+    /// Returns the position of the cursor as `(row, col)`, 0-based, asking it to the terminal
+    /// with the Device Status Report escape sequence (`ESC [ 6 n`).
     /// 
-    /// Returns `self` for chaining.
+    /// Echo and canonical mode are temporarily disabled while waiting for the reply, and restored before returning.
+    /// Any input typed in the meantime is kept and returned by subsequent reads.
+    /// If the terminal does not reply within half a second, an error of kind [`TimedOut`] is returned.
     /// 
-    /// [`Console::switch_to`]: crate::Console::switch_to
-    pub fn switch(&self) -> io::Result<&Self> {
-        self.console.switch_to(self.number)?;
-        Ok(self)
+    /// [`TimedOut`]: std::io::ErrorKind::TimedOut
+    pub fn cursor_position(&mut self) -> Result<(u16, u16)> {
+        let mut termios = self.termios.clone();
+        termios.local_flags &= !(LocalFlags::ICANON | LocalFlags::ECHO);
+        termios.control_chars[SpecialCharacterIndices::VMIN as usize] = 0;
+        termios.control_chars[SpecialCharacterIndices::VTIME as usize] =
+            units::decisecs_from_duration(CURSOR_REPORT_TIMEOUT, u8::MAX as u32)? as u8;
+
+        self.with_termios(termios, |vt| {
+            vt.write_all(b"\x1b[6n")?;
+            vt.flush()?;
+
+            let deadline = Instant::now() + CURSOR_REPORT_TIMEOUT;
+            loop {
+                if let Some((range, row, col)) = find_cursor_report(&vt.read_buf) {
+                    vt.read_buf.drain(range);
+                    return Ok((row.saturating_sub(1), col.saturating_sub(1)));
+                }
+                if Instant::now() >= deadline || !vt.fill_read_buf()? {
+                    return Err(VtError::new(io::ErrorKind::TimedOut, "The terminal did not report the cursor position"));
+                }
+            }
+        })
     }
 
-    /// Clears the terminal.
+    /// Returns an iterator over the lines typed in this terminal, without the trailing newline.
     /// 
-    /// Returns `self` for chaining.
-    pub fn clear(&mut self) -> io::Result<&mut Self> {
-        write!(self, "\x1b[H\x1b[J")?;
-        Ok(self)
+    /// The terminal should be in canonical mode (the default), where the kernel delivers input one line at a time.
+    /// Each call to `next` blocks until a full line is available, and yields an error if reading fails
+    /// or if the line is not valid UTF-8. The iterator ends when the terminal reports EOF,
+    /// which is rare since EOF generation is disabled by default.
+    pub fn lines(&mut self) -> Lines<'_, 'a> {
+        Lines {
+            vt: self
+        }
     }
 
-    /// Sets the blank timer for this terminal. A value of `0` disables the timer.
+    /// Reads bytes until the given delimiter is found, returning them together with the delimiter.
     /// 
-    /// Returns `self` for chaining.
-    pub fn set_blank_timer(&mut self, timer: u32) -> io::Result<&mut Self> {
-        write!(self, "\x1b[9;{}]", timer)?;
-        Ok(self)
+    /// This blocks until the delimiter is read. If EOF is reached before, all the bytes read so far
+    /// are returned without the delimiter, and an empty vector signals that no bytes were left at all.
+    /// Input is read in chunks, so bytes past the delimiter are kept internally
+    /// and returned by subsequent reads.
+    pub fn read_until(&mut self, delimiter: u8) -> Result<Vec<u8>> {
+        loop {
+            if let Some(i) = self.read_buf.iter().position(|b| *b == delimiter) {
+                return Ok(self.read_buf.drain(..=i).collect());
+            }
+            if !self.fill_read_buf()? {
+                return Ok(std::mem::take(&mut self.read_buf));
+            }
+        }
     }
 
-    /// Blanks or unlanks the terminal.
+    /// Returns an iterator over the characters typed in this terminal, decoded as UTF-8.
     /// 
-    /// Returns `self` for chaining.
-    pub fn blank(&mut self, blank: bool) -> io::Result<&mut Self> {
-        
-        // If the console blanking timer is disabled, the ioctl below will fail,
-        // so we need to enable it just for the time needed for the ioctl to work.
-        let needs_timer_reset = if blank && self.console.blank_timer()? == 0 {
-            self.set_blank_timer(1)?;
-            true
-        } else {
-            false
-        };
+    /// Multi-byte characters are reassembled even if they are split across reads. Invalid sequences
+    /// yield an error of kind [`InvalidData`], after which decoding resumes from the next byte.
+    /// Use [`Vt::set_utf8`] to make sure that the terminal actually produces UTF-8.
+    /// 
+    /// [`InvalidData`]: std::io::ErrorKind::InvalidData
+    /// [`Vt::set_utf8`]: crate::Vt::set_utf8
+    pub fn chars(&mut self) -> Chars<'_, 'a> {
+        Chars {
+            vt: self
+        }
+    }
 
-        let mut arg = if blank { ffi::TIOCL_BLANKSCREEN } else { ffi::TIOCL_UNBLANKSCREEN };
-        ffi::tioclinux(self.file.as_raw_fd(), &mut arg)?;
+    /// Reads the next UTF-8 character, returning `None` at EOF.
+    fn read_char(&mut self) -> Result<Option<char>> {
+        loop {
+            if let Some(&first) = self.read_buf.first() {
+                let len = match first {
+                    0x00..=0x7F => 1,
+                    0xC2..=0xDF => 2,
+                    0xE0..=0xEF => 3,
+                    0xF0..=0xF4 => 4,
+                    _ => 0
+                };
+                if len > 0 && self.read_buf.len() >= len {
+                    if let Some(c) = std::str::from_utf8(&self.read_buf[..len]).ok().and_then(|s| s.chars().next()) {
+                        self.read_buf.drain(..len);
+                        return Ok(Some(c));
+                    }
+                }
+                if len == 0 || self.read_buf.len() >= len {
+                    self.read_buf.remove(0);
+                    return Err(VtError::new(io::ErrorKind::InvalidData, format!("Invalid UTF-8 sequence starting with 0x{:02x}", first)));
+                }
+            }
 
-        // Disable the blank timer if originally it was disabled
-        if needs_timer_reset {
-            self.set_blank_timer(0)?;
+            if !self.fill_read_buf()? {
+                if self.read_buf.is_empty() {
+                    return Ok(None);
+                }
+                self.read_buf.clear();
+                return Err(VtError::new(io::ErrorKind::InvalidData, "Incomplete UTF-8 sequence at EOF"));
+            }
         }
+    }
 
-        Ok(self)
+    /// Reads a chunk of input into the internal buffer, returning `false` at EOF.
+    fn fill_read_buf(&mut self) -> Result<bool> {
+        let mut chunk = [0u8; 1024];
+        loop {
+            match self.file.read(&mut chunk) {
+                Ok(0) => return Ok(false),
+                Ok(n) => {
+                    self.read_buf.extend_from_slice(&chunk[..n]);
+                    return Ok(true);
+                },
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {},
+                Err(e) => return Err(e.into())
+            }
+        }
     }
 
-    /// Enables or disables the echo of the characters typed by the user.
+    /// Applies the given configuration to the terminal.
     /// 
     /// Returns `self` for chaining.
-    pub fn set_echo(&mut self, echo: bool) -> io::Result<&mut Self> {
-        if echo {
+    pub fn apply_config(&mut self, config: &TermiosConfig) -> Result<&mut Self> {
+        if config.echo {
             self.termios.local_flags |= LocalFlags::ECHO;
         } else {
             self.termios.local_flags &= !LocalFlags::ECHO;
         }
+        if config.canonical {
+            self.termios.local_flags |= LocalFlags::ICANON;
+        } else {
+            self.termios.local_flags &= !LocalFlags::ICANON;
+        }
+        self.termios.control_chars[SpecialCharacterIndices::VEOF as usize] = if config.eof { 4 } else { 0 };
+
+        if config.signals.is_empty() {
+            self.termios.local_flags &= !LocalFlags::ISIG;
+            self.update_termios()?;
+            Ok(self)
+        } else {
+            self.signals(config.signals)
+        }
+    }
+
+    /// Sets the maximum time a read waits for input, rounded up to tenths of a second (`VTIME`).
+    /// `None` means waiting indefinitely.
+    /// 
+    /// Combined with [`Vt::set_min_bytes`], a read returns as soon as the minimum number of bytes is available,
+    /// or when the timeout expires after the last received byte (or from the start of the read, if the minimum is `0`).
+    /// This only applies in non-canonical mode.
+    /// 
+    /// Returns an error of kind [`InvalidInput`] if the timeout is longer than 25.5 seconds.
+    /// 
+    /// Returns `self` for chaining.
+    /// 
+    /// [`Vt::set_min_bytes`]: crate::Vt::set_min_bytes
+    /// [`InvalidInput`]: std::io::ErrorKind::InvalidInput
+    pub fn set_read_timeout(&mut self, timeout: Option<Duration>) -> Result<&mut Self> {
+        let decisecs = match timeout {
+            Some(timeout) => units::decisecs_from_duration(timeout, u8::MAX as u32)?,
+            None => 0
+        };
+        self.termios.control_chars[SpecialCharacterIndices::VTIME as usize] = decisecs as u8;
         self.update_termios()?;
 
         Ok(self)
     }
 
-    /// Returns a value indicating whether this terminal has echo enabled or not.
-    pub fn is_echo_enabled(&self) -> bool {
-        self.termios.local_flags.contains(LocalFlags::ECHO)
+    /// Sets the minimum number of bytes a read waits for (`VMIN`).
+    /// See [`Vt::set_read_timeout`] for how this interacts with the read timeout.
+    /// This only applies in non-canonical mode.
+    /// 
+    /// Returns `self` for chaining.
+    /// 
+    /// [`Vt::set_read_timeout`]: crate::Vt::set_read_timeout
+    pub fn set_min_bytes(&mut self, n: u8) -> Result<&mut Self> {
+        self.termios.control_chars[SpecialCharacterIndices::VMIN as usize] = n;
+        self.update_termios()?;
+
+        Ok(self)
     }
 
     /// Enables or disables signal generation from terminal.
     /// 
     /// Returns `self` for chaining.
-    pub fn signals(&mut self, signals: VtSignals) -> io::Result<&mut Self> {
+    pub fn signals(&mut self, signals: VtSignals) -> Result<&mut Self> {
         
         // Since we created the vt with signals disabled, we need to enable them
         self.termios.local_flags |= LocalFlags::ISIG;
@@ -232,34 +1770,454 @@ impl<'a> Vt<'a> {
         Ok(self)
     }
 
+    /// Waits until all the output written to this terminal has been transmitted.
+    /// 
+    /// Flushing a terminal with [`Write::flush`] does not wait for the kernel to process the output,
+    /// so this should be called before switching to another terminal or blanking the screen,
+    /// if the output must be visible before that happens. See also [`Vt::set_drain_on_flush`].
+    /// 
+    /// Returns `self` for chaining.
+    /// 
+    /// [`Write::flush`]: std::io::Write::flush
+    /// [`Vt::set_drain_on_flush`]: crate::Vt::set_drain_on_flush
+    pub fn drain(&mut self) -> Result<&mut Self> {
+        tcdrain(self.file.as_raw_fd())?;
+        Ok(self)
+    }
+
+    /// Sets whether [`Write::flush`] also waits for the output to be transmitted, like [`Vt::drain`] does.
+    /// This is disabled by default. Note that waiting blocks the thread even if the terminal is in non-blocking mode.
+    /// 
+    /// Returns `self` for chaining.
+    /// 
+    /// [`Write::flush`]: std::io::Write::flush
+    /// [`Vt::drain`]: crate::Vt::drain
+    pub fn set_drain_on_flush(&mut self, drain: bool) -> &mut Self {
+        self.drain_on_flush = drain;
+        self
+    }
+
     /// Flushes the internal buffers of the terminal.
-    pub fn flush_buffers(&mut self, t: VtFlushType) -> io::Result<&mut Self> {
+    pub fn flush_buffers(&mut self, t: VtFlushType) -> Result<&mut Self> {
         let action = match t {
             VtFlushType::Incoming => FlushArg::TCIFLUSH,
             VtFlushType::Outgoing => FlushArg::TCOFLUSH,
             VtFlushType::Both => FlushArg::TCIOFLUSH
         };
-        tcflush(self.file.as_raw_fd(), action)
-            .map_err(|e| io::Error::from_raw_os_error(e.as_errno().unwrap_or(nix::errno::Errno::UnknownErrno) as i32))?;
+        tcflush(self.file.as_raw_fd(), action)?;
+
+        Ok(self)
+    }
+
+    /// Returns the id of the foreground process group of this terminal.
+    pub fn foreground_process_group(&self) -> Result<i32> {
+        tcgetpgrp(self.file.as_raw_fd())
+            .map(|pgid| pgid.as_raw())
+            .map_err(VtError::from)
+    }
+
+    /// Makes the process group with the given id the foreground process group of this terminal.
+    /// 
+    /// The foreground process group is the one receiving the signals generated by the terminal
+    /// (like `SIGINT` or `SIGTSTP`), so changing it changes where those signals are delivered.
+    /// The calling process must belong to the same session of the new process group,
+    /// and the terminal must be its controlling terminal.
+    /// 
+    /// Returns `self` for chaining.
+    pub fn set_foreground_process_group(&mut self, pgid: i32) -> Result<&mut Self> {
+        tcsetpgrp(self.file.as_raw_fd(), Pid::from_raw(pgid))?;
+        Ok(self)
+    }
+
+    /// Duplicates all the output written to this terminal from now on to the given writer,
+    /// replacing any writer previously installed. Useful to capture the output for logging or testing purposes.
+    /// 
+    /// Data is first written to the terminal, and only the bytes actually accepted by the terminal
+    /// are then written to the tee writer. If writing to the tee writer fails,
+    /// the writer is detached and the error discarded, so that a failing log never disrupts the terminal output:
+    /// [`Vt::untee`] returning `None` can be used to detect this condition.
+    /// 
+    /// [`Vt::untee`]: crate::Vt::untee
+    pub fn tee(&mut self, writer: Box<dyn Write + Send>) -> &mut Self {
+        self.tee = Some(writer);
+        self
+    }
+
+    /// Stops duplicating the output of this terminal, returning the writer installed with [`Vt::tee`], if any.
+    /// 
+    /// [`Vt::tee`]: crate::Vt::tee
+    pub fn untee(&mut self) -> Option<Box<dyn Write + Send>> {
+        self.tee.take()
+    }
+
+    fn write_tee(&mut self, bufs: &[IoSlice], mut len: usize) {
+        if let Some(ref mut tee) = self.tee {
+            for buf in bufs {
+                if len == 0 {
+                    break;
+                }
+                let n = len.min(buf.len());
+                if tee.write_all(&buf[..n]).is_err() {
+                    self.tee = None;
+                    break;
+                }
+                len -= n;
+            }
+        }
+    }
+
+    /// Returns the baud rate of this terminal, in bits per second.
+    /// This is only meaningful for terminals attached to a serial line.
+    pub fn baud_rate(&self) -> Result<u32> {
+        let speed = cfgetospeed(&self.termios);
+        BAUD_RATES.iter()
+            .find(|(_, b)| *b == speed)
+            .map(|(n, _)| *n)
+            .ok_or_else(|| VtError::new(io::ErrorKind::InvalidData, "Unknown baud rate"))
+    }
 
+    /// Sets both the input and output baud rate of this terminal, in bits per second.
+    /// This is only meaningful for terminals attached to a serial line, and is harmless otherwise.
+    /// 
+    /// Returns an error of kind [`InvalidInput`] if the given rate is not supported.
+    /// 
+    /// Returns `self` for chaining.
+    /// 
+    /// [`InvalidInput`]: std::io::ErrorKind::InvalidInput
+    pub fn set_baud_rate(&mut self, rate: u32) -> Result<&mut Self> {
+        let speed = BAUD_RATES.iter()
+            .find(|(n, _)| *n == rate)
+            .map(|(_, b)| *b)
+            .ok_or_else(|| VtError::new(io::ErrorKind::InvalidInput, format!("Unsupported baud rate: {}", rate)))?;
+        cfsetspeed(&mut self.termios, speed)?;
+        self.update_termios()?;
+
+        Ok(self)
+    }
+
+    /// Returns the keycode the given keyboard scancode is translated to.
+    /// 
+    /// Scancodes are the raw codes sent by the keyboard, which the kernel translates to keycodes
+    /// before looking them up in the keymap: this is the table managed by the `setkeycodes` utility.
+    /// Like in `setkeycodes`, extended scancodes of AT keyboards, sent as `0xe0` followed by a second byte `xx`,
+    /// are identified by `0x80 + xx`.
+    pub fn keycode_for_scancode(&self, scancode: u32) -> Result<u32> {
+        let mut arg = ffi::KbKeycode { scancode, keycode: 0 };
+        ffi::kdgetkeycode(self.file.as_raw_fd(), &mut arg)?;
+        Ok(arg.keycode)
+    }
+
+    /// Changes the keycode the given keyboard scancode is translated to.
+    /// 
+    /// This is useful to make keys sending unknown scancodes usable. Note that this operates
+    /// on the scancode to keycode translation, which comes before (and is independent from)
+    /// the keymap mapping keycodes to the actual symbols. The translation table is shared by all the terminals,
+    /// and changing it requires the `CAP_SYS_TTY_CONFIG` capability.
+    /// Scancodes are numbered as described in [`Vt::keycode_for_scancode`].
+    /// 
+    /// Returns an error of kind [`InvalidInput`] if the keycode is out of range.
+    /// 
+    /// Returns `self` for chaining.
+    /// 
+    /// [`Vt::keycode_for_scancode`]: crate::Vt::keycode_for_scancode
+    /// [`InvalidInput`]: std::io::ErrorKind::InvalidInput
+    pub fn set_keycode_for_scancode(&mut self, scancode: u32, keycode: u32) -> Result<&mut Self> {
+        if keycode > ffi::KEY_MAX {
+            return Err(VtError::new(io::ErrorKind::InvalidInput, format!("Invalid keycode: {}", keycode)));
+        }
+        let arg = ffi::KbKeycode { scancode, keycode };
+        ffi::kdsetkeycode(self.file.as_raw_fd(), &arg)?;
         Ok(self)
     }
 
     /// Configures the terminal in raw mode: input is available character by character,
     /// echoing is disabled, and all special processing of terminal input and output characters is disabled.
-    pub fn raw(&mut self) -> io::Result<&mut Self> {
+    pub fn raw(&mut self) -> Result<&mut Self> {
         cfmakeraw(&mut self.termios);
         self.update_termios()?;
         Ok(self)
     }
 
+    /// Puts this terminal in raw mode like [`Vt::raw`] does, returning a guard that restores
+    /// the previous terminal attributes when dropped. The guard dereferences to the terminal itself.
+    /// 
+    /// [`Vt::raw`]: crate::Vt::raw
+    pub fn raw_mode(&mut self) -> Result<RawGuard<'_, 'a>> {
+        let previous = self.termios.clone();
+        self.raw()?;
+        Ok(RawGuard { vt: self, previous })
+    }
+
+    /// Brings this terminal back to a usable state, like the `reset` utility does.
+    /// 
+    /// The terminal is put back in text mode, the keyboard in translated mode ([`KeyboardMode::Unicode`]
+    /// if the terminal is configured for UTF-8 input, [`KeyboardMode::Xlate`] otherwise),
+    /// and the terminal attributes are restored to sane values like `stty sane` does:
+    /// canonical mode, echo, signals and output processing are enabled, and the default control characters are used.
+    /// Then the full reset sequence (`ESC c`) is sent, which clears the screen and restores colors, cursor,
+    /// scroll region and character sets, and any pending input is discarded.
+    /// 
+    /// This is meant for recovering from crashes of programs which left the terminal in an unknown state.
+    /// 
+    /// Returns `self` for chaining.
+    pub fn reset(&mut self) -> Result<&mut Self> {
+        self.set_kd_mode(KdMode::Text)?;
+        let utf8 = self.termios.input_flags.contains(InputFlags::IUTF8);
+        self.set_keyboard_mode(if utf8 { KeyboardMode::Unicode } else { KeyboardMode::Xlate })?;
+
+        let termios = &mut self.termios;
+        termios.input_flags |= InputFlags::BRKINT | InputFlags::ICRNL | InputFlags::IMAXBEL;
+        termios.input_flags &= !(InputFlags::IGNBRK | InputFlags::INLCR | InputFlags::IGNCR | InputFlags::ISTRIP | InputFlags::IXOFF);
+        termios.output_flags |= OutputFlags::OPOST | OutputFlags::ONLCR;
+        termios.output_flags &= !(OutputFlags::OCRNL | OutputFlags::ONOCR | OutputFlags::ONLRET);
+        termios.local_flags |= LocalFlags::ICANON | LocalFlags::ISIG | LocalFlags::IEXTEN | LocalFlags::ECHO
+            | LocalFlags::ECHOE | LocalFlags::ECHOK | LocalFlags::ECHOCTL | LocalFlags::ECHOKE;
+        termios.local_flags &= !(LocalFlags::ECHONL | LocalFlags::NOFLSH | LocalFlags::TOSTOP);
+        for (index, value) in SANE_CONTROL_CHARS {
+            termios.control_chars[*index as usize] = *value;
+        }
+        self.update_termios()?;
+
+        self.write_all(b"\x1bc")?;
+        self.read_buf.clear();
+        self.flush_buffers(VtFlushType::Incoming)?;
+
+        Ok(self)
+    }
+
+}
+
+/// Guard returned by [`Vt::graphics_mode`], which restores text mode when dropped.
+/// 
+/// [`Vt::graphics_mode`]: crate::Vt::graphics_mode
+pub struct GraphicsModeGuard<'v, 'a> {
+    vt: &'v mut Vt<'a>
+}
+
+impl<'v, 'a> Deref for GraphicsModeGuard<'v, 'a> {
+    type Target = Vt<'a>;
+
+    fn deref(&self) -> &Vt<'a> {
+        self.vt
+    }
+}
+
+impl<'v, 'a> DerefMut for GraphicsModeGuard<'v, 'a> {
+    fn deref_mut(&mut self) -> &mut Vt<'a> {
+        self.vt
+    }
+}
+
+impl<'v, 'a> Drop for GraphicsModeGuard<'v, 'a> {
+    fn drop(&mut self) {
+        let _ = self.vt.set_kd_mode(KdMode::Text);
+    }
+}
+
+/// Guard returned by [`Vt::keyboard_mode_guard`], which restores the previous keyboard mode when dropped.
+/// 
+/// [`Vt::keyboard_mode_guard`]: crate::Vt::keyboard_mode_guard
+pub struct KeyboardModeGuard<'v, 'a> {
+    vt: &'v mut Vt<'a>,
+    previous: KeyboardMode
+}
+
+impl<'v, 'a> Deref for KeyboardModeGuard<'v, 'a> {
+    type Target = Vt<'a>;
+
+    fn deref(&self) -> &Vt<'a> {
+        self.vt
+    }
+}
+
+impl<'v, 'a> DerefMut for KeyboardModeGuard<'v, 'a> {
+    fn deref_mut(&mut self) -> &mut Vt<'a> {
+        self.vt
+    }
+}
+
+impl<'v, 'a> Drop for KeyboardModeGuard<'v, 'a> {
+    fn drop(&mut self) {
+        let _ = self.vt.set_keyboard_mode(self.previous);
+    }
+}
+
+/// Guard returned by [`Vt::raw_mode`], which restores the previous terminal attributes when dropped.
+/// 
+/// [`Vt::raw_mode`]: crate::Vt::raw_mode
+pub struct RawGuard<'v, 'a> {
+    vt: &'v mut Vt<'a>,
+    previous: Termios
+}
+
+impl<'v, 'a> Deref for RawGuard<'v, 'a> {
+    type Target = Vt<'a>;
+
+    fn deref(&self) -> &Vt<'a> {
+        self.vt
+    }
+}
+
+impl<'v, 'a> DerefMut for RawGuard<'v, 'a> {
+    fn deref_mut(&mut self) -> &mut Vt<'a> {
+        self.vt
+    }
+}
+
+impl<'v, 'a> Drop for RawGuard<'v, 'a> {
+    fn drop(&mut self) {
+        self.vt.termios = self.previous.clone();
+        let _ = self.vt.update_termios();
+    }
+}
+
+/// Finds the driver handling the given device numbers in the contents of `/proc/tty/drivers`,
+/// whose lines contain the driver name, the device path, the major number, the range of minor numbers and the type.
+fn find_tty_driver(drivers: &str, major: u32, minor: u32) -> Option<String> {
+    drivers.lines().find_map(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 5 || fields[2].parse::<u32>().ok()? != major {
+            return None;
+        }
+        let (first, last) = match fields[3].split_once('-') {
+            Some((first, last)) => (first.parse::<u32>().ok()?, last.parse::<u32>().ok()?),
+            None => {
+                let n = fields[3].parse::<u32>().ok()?;
+                (n, n)
+            }
+        };
+        if minor < first || minor > last {
+            return None;
+        }
+        Some(if fields[4] == "console" { "vt".to_string() } else { fields[0].to_string() })
+    })
+}
+
+/// Finds a cursor position report (`ESC [ row ; col R`) in the given input,
+/// returning its range and the 1-based coordinates. Missing coordinates default to 1.
+fn find_cursor_report(buf: &[u8]) -> Option<(std::ops::Range<usize>, u16, u16)> {
+    (0..buf.len()).find_map(|start| {
+        let rest = buf[start..].strip_prefix(b"\x1b[")?;
+        let end = rest.iter().position(|b| !(b.is_ascii_digit() || *b == b';'))?;
+        if rest[end] != b'R' {
+            return None;
+        }
+        let params = std::str::from_utf8(&rest[..end]).ok()?;
+        let mut parts = params.split(';').map(|p| if p.is_empty() { Some(1) } else { p.parse::<u16>().ok() });
+        match (parts.next().flatten(), parts.next().flatten(), parts.next()) {
+            (Some(row), Some(col), None) => Some((start..start + 2 + end + 1, row, col)),
+            _ => None
+        }
+    })
+}
+
+/// Converts a frequency to the period of the PC speaker timer.
+fn tone_period(frequency_hz: u32) -> Result<c_ulong> {
+    let period = ffi::PIT_TICK_RATE.checked_div(frequency_hz).unwrap_or(0);
+    if period == 0 || period > u16::MAX as u32 {
+        return Err(VtError::new(io::ErrorKind::InvalidInput, format!("Unsupported tone frequency: {} Hz", frequency_hz)));
+    }
+    Ok(period.into())
+}
+
+/// Approximates an RGB color with the closest color of the 256-color palette,
+/// choosing between the 6x6x6 color cube and the grayscale ramp.
+fn rgb_to_ansi256((r, g, b): (u8, u8, u8)) -> u8 {
+    const LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+    let cube_index = |c: u8| LEVELS.iter().enumerate().min_by_key(|(_, l)| (c as i32 - **l as i32).abs()).unwrap().0;
+    let (ri, gi, bi) = (cube_index(r), cube_index(g), cube_index(b));
+    let cube_color = (LEVELS[ri], LEVELS[gi], LEVELS[bi]);
+
+    let average = (r as u32 + g as u32 + b as u32) / 3;
+    let gray_index = (average.saturating_sub(3) / 10).min(23) as u8;
+    let gray_level = 8 + gray_index * 10;
+
+    let distance = |(cr, cg, cb): (u8, u8, u8)| {
+        let d = |a: u8, b: u8| (a as i32 - b as i32).pow(2);
+        d(cr, r) + d(cg, g) + d(cb, b)
+    };
+    if distance((gray_level, gray_level, gray_level)) < distance(cube_color) {
+        232 + gray_index
+    } else {
+        16 + 36 * ri as u8 + 6 * gi as u8 + bi as u8
+    }
+}
+
+/// Iterator over the lines of a [`Vt`], returned by [`Vt::lines`].
+/// 
+/// [`Vt`]: crate::Vt
+/// [`Vt::lines`]: crate::Vt::lines
+pub struct Lines<'v, 'a> {
+    vt: &'v mut Vt<'a>
+}
+
+impl<'v, 'a> Iterator for Lines<'v, 'a> {
+    type Item = Result<String>;
+
+    fn next(&mut self) -> Option<Result<String>> {
+        let mut line = match self.vt.read_until(b'\n') {
+            Ok(line) => line,
+            Err(e) => return Some(Err(e))
+        };
+        if line.is_empty() {
+            return None;
+        }
+        if line.last() == Some(&b'\n') {
+            line.pop();
+            if line.last() == Some(&b'\r') {
+                line.pop();
+            }
+        }
+        Some(String::from_utf8(line).map_err(|e| VtError::new(io::ErrorKind::InvalidData, e)))
+    }
+}
+
+/// Iterator over the characters of a [`Vt`], returned by [`Vt::chars`].
+/// 
+/// [`Vt`]: crate::Vt
+/// [`Vt::chars`]: crate::Vt::chars
+pub struct Chars<'v, 'a> {
+    vt: &'v mut Vt<'a>
+}
+
+impl<'v, 'a> Iterator for Chars<'v, 'a> {
+    type Item = Result<char>;
+
+    fn next(&mut self) -> Option<Result<char>> {
+        self.vt.read_char().transpose()
+    }
 }
 
 impl<'a> Drop for Vt<'a> {
     fn drop(&mut self) {
+        // Wait for all the pending output to be transmitted before releasing the vt,
+        // otherwise anything written right before the drop might be lost.
+        let _ = self.flush();
+        let _ = tcdrain(self.file.as_raw_fd());
+
+        // Errors are ignored, since we have no way to recover from them here
+        match self.drop_policy {
+            DropPolicy::RestoreTermios => {
+                let _ = tcsetattr(self.file.as_raw_fd(), SetArg::TCSANOW, &self.initial_termios);
+            },
+            DropPolicy::SwitchBack => {
+                if let Some(previous) = self.previous_vt {
+                    if self.is_active().unwrap_or(false) {
+                        let _ = self.console.switch_to(previous);
+                    }
+                }
+            },
+            DropPolicy::Deallocate | DropPolicy::KeepAllocated => {}
+        }
+
+        // The kernel refuses to disallocate a vt that is still open, so close our descriptor first.
+        // The file is never used again after this point.
+        unsafe { ManuallyDrop::drop(&mut self.file) };
+
         // Notify the kernel that we do not need the vt anymore.
-        // Note we don't check the return value because we have no way to recover from a closing error.
-        let _ = self.console.disallocate_vt(self.number);
+        if let DropPolicy::Deallocate | DropPolicy::SwitchBack = self.drop_policy {
+            let _ = self.console.disallocate_vt(self.number);
+        }
     }
 }
 
@@ -275,58 +2233,144 @@ impl<'a> AsRawFd for Vt<'a> {
     }
 }
 
-/// Delegates the implementation of [`Read`] to the underlying [`File`].
+impl<'a> AsFd for Vt<'a> {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.file.as_fd()
+    }
+}
+
+/// Allows registering a [`Vt`] in a `mio` event loop. Remember to call [`Vt::set_nonblocking`] first.
 /// 
-/// [`Read`]: std::io::Read
-/// [`File`]: std::fs::File
-impl<'a> Read for Vt<'a> {
+/// [`Vt`]: crate::Vt
+/// [`Vt::set_nonblocking`]: crate::Vt::set_nonblocking
+#[cfg(feature = "mio")]
+impl<'a> mio::event::Source for Vt<'a> {
 
-    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        self.file.read(buf)
+    fn register(&mut self, registry: &mio::Registry, token: mio::Token, interests: mio::Interest) -> io::Result<()> {
+        mio::unix::SourceFd(&self.file.as_raw_fd()).register(registry, token, interests)
     }
 
-    fn read_vectored(&mut self, bufs: &mut [IoSliceMut]) -> io::Result<usize> {
-        self.file.read_vectored(bufs)
+    fn reregister(&mut self, registry: &mio::Registry, token: mio::Token, interests: mio::Interest) -> io::Result<()> {
+        mio::unix::SourceFd(&self.file.as_raw_fd()).reregister(registry, token, interests)
     }
 
-    fn read_to_end(&mut self, buf: &mut Vec<u8>) -> io::Result<usize> {
-        self.file.read_to_end(buf)
+    fn deregister(&mut self, registry: &mio::Registry) -> io::Result<()> {
+        mio::unix::SourceFd(&self.file.as_raw_fd()).deregister(registry)
     }
 
-    fn read_to_string(&mut self, buf: &mut String) -> io::Result<usize> {
-        self.file.read_to_string(buf)
+}
+
+/// Delegates the implementation of [`Read`] to the underlying [`File`],
+/// after returning any data left over by [`Vt::read_until`].
+/// 
+/// [`Read`]: std::io::Read
+/// [`File`]: std::fs::File
+/// [`Vt::read_until`]: crate::Vt::read_until
+impl<'a> Read for Vt<'a> {
+
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.read_buf.is_empty() {
+            return self.file.read(buf);
+        }
+        let n = buf.len().min(self.read_buf.len());
+        buf[..n].copy_from_slice(&self.read_buf[..n]);
+        self.read_buf.drain(..n);
+        Ok(n)
     }
 
-    fn read_exact(&mut self, buf: &mut [u8]) -> io::Result<()> {
-        self.file.read_exact(buf)
+    fn read_vectored(&mut self, bufs: &mut [IoSliceMut]) -> io::Result<usize> {
+        if self.read_buf.is_empty() {
+            return self.file.read_vectored(bufs);
+        }
+        let buf = bufs.iter_mut().find(|b| !b.is_empty()).map_or(&mut [][..], |b| &mut **b);
+        self.read(buf)
     }
 
 }
 
-/// Delegates the implementation of [`Write`] to the underlying [`File`].
+/// Delegates the implementation of [`Write`] to the underlying [`File`],
+/// duplicating the output to the writer installed with [`Vt::tee`], if any.
+/// Vectored writes are submitted to the kernel with a single `writev` call.
 /// 
 /// [`Write`]: std::io::Write
 /// [`File`]: std::fs::File
+/// [`Vt::tee`]: crate::Vt::tee
 impl<'a> Write for Vt<'a> {
 
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        self.file.write(buf)
+        self.check_writable()?;
+        let n = self.file.write(buf)?;
+        self.write_tee(&[IoSlice::new(buf)], n);
+        Ok(n)
     }
 
     fn flush(&mut self) -> io::Result<()> {
-        self.file.flush()
+        self.file.flush()?;
+        if self.drain_on_flush {
+            tcdrain(self.file.as_raw_fd()).map_err(VtError::from)?;
+        }
+        if let Some(ref mut tee) = self.tee {
+            if tee.flush().is_err() {
+                self.tee = None;
+            }
+        }
+        Ok(())
     }
 
     fn write_vectored(&mut self, bufs: &[IoSlice]) -> io::Result<usize> {
-        self.file.write_vectored(bufs)
+        self.check_writable()?;
+        let n = self.file.write_vectored(bufs)?;
+        self.write_tee(bufs, n);
+        Ok(n)
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_tty_driver() {
+        let drivers = "/dev/tty             /dev/tty        5       0 system:/dev/tty\n\
+                       /dev/vc/0            /dev/vc/0       4       0 system:vtmaster\n\
+                       serial               /dev/ttyS       4 64-111 serial\n\
+                       unknown              /dev/tty        4 1-63 console\n";
+        assert_eq!(find_tty_driver(drivers, 4, 1).as_deref(), Some("vt"));
+        assert_eq!(find_tty_driver(drivers, 4, 63).as_deref(), Some("vt"));
+        assert_eq!(find_tty_driver(drivers, 4, 70).as_deref(), Some("serial"));
+        assert_eq!(find_tty_driver(drivers, 4, 0).as_deref(), Some("/dev/vc/0"));
+        assert_eq!(find_tty_driver(drivers, 4, 200), None);
+        assert_eq!(find_tty_driver(drivers, 136, 0), None);
     }
 
-    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
-        self.file.write_all(buf)
+    #[test]
+    fn finds_cursor_report() {
+        assert_eq!(find_cursor_report(b"\x1b[12;40R"), Some((0..8, 12, 40)));
+        assert_eq!(find_cursor_report(b"\x1b[;R"), Some((0..4, 1, 1)));
+        assert_eq!(find_cursor_report(b"\x1b[5;R"), Some((0..5, 5, 1)));
     }
 
-    fn write_fmt(&mut self, fmt: fmt::Arguments) -> io::Result<()> {
-        self.file.write_fmt(fmt)
+    #[test]
+    fn finds_cursor_report_after_other_input() {
+        assert_eq!(find_cursor_report(b"ab\x1b[A\x1b[3;7Rcd"), Some((5..11, 3, 7)));
+        assert_eq!(find_cursor_report(b"\x1b\x1b[3;7R"), Some((1..7, 3, 7)));
+        assert_eq!(find_cursor_report(b"\x1b[1;2;3R\x1b[4;5R"), Some((8..14, 4, 5)));
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn ignores_partial_cursor_report() {
+        assert_eq!(find_cursor_report(b""), None);
+        assert_eq!(find_cursor_report(b"\x1b"), None);
+        assert_eq!(find_cursor_report(b"\x1b[12"), None);
+        assert_eq!(find_cursor_report(b"\x1b[12;4"), None);
+        assert_eq!(find_cursor_report(b"\x1b[99999;1R"), None);
+
+        // The rest of a split report completes it
+        let mut buf = b"\x1b[12;".to_vec();
+        assert_eq!(find_cursor_report(&buf), None);
+        buf.extend_from_slice(b"4R");
+        assert_eq!(find_cursor_report(&buf), Some((0..7, 12, 4)));
+    }
+
+}
@@ -0,0 +1,86 @@
+use std::fs::File;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::time::{Duration, Instant};
+use nix::errno::Errno;
+use nix::poll::{poll, PollFd, PollFlags};
+use crate::error::Result;
+use crate::console::{ACTIVE_TTY_PATH, read_active_tty};
+use crate::vt::VtNumber;
+
+/// Watches for changes of the active terminal, through the notifications the kernel sends
+/// on `/sys/class/tty/tty0/active`.
+/// 
+/// Unlike [`Console::wait_for_switch`], this does not need access to the console device,
+/// so it works for unprivileged processes, and any number of watchers can be active at the same time.
+/// 
+/// The watcher is also an iterator, whose `next` blocks until the active terminal changes.
+/// 
+/// [`Console::wait_for_switch`]: crate::Console::wait_for_switch
+pub struct VtWatcher {
+    file: File,
+    active: VtNumber
+}
+
+impl VtWatcher {
+
+    /// Creates a new watcher, starting from the terminal that is currently active.
+    pub fn new() -> Result<VtWatcher> {
+        let mut file = File::open(ACTIVE_TTY_PATH)?;
+        let active = read_active_tty(&mut file)?;
+        Ok(VtWatcher {
+            file,
+            active
+        })
+    }
+
+    /// Returns the active terminal, as of the last change observed by this watcher.
+    pub fn active(&self) -> VtNumber {
+        self.active
+    }
+
+    /// Blocks until the active terminal changes, or until the given timeout expires.
+    /// Returns the newly active terminal, or `None` if the timeout expired.
+    pub fn wait(&mut self, timeout: Option<Duration>) -> Result<Option<VtNumber>> {
+        let deadline = timeout.map(|t| Instant::now() + t);
+        loop {
+            let millis = match deadline {
+                Some(deadline) => deadline.saturating_duration_since(Instant::now()).as_millis().min(i32::MAX as u128) as i32,
+                None => -1
+            };
+
+            let mut fds = [PollFd::new(self.file.as_raw_fd(), PollFlags::POLLPRI | PollFlags::POLLERR)];
+            match poll(&mut fds, millis) {
+                Ok(0) => return Ok(None),
+                Ok(_) => {},
+                Err(nix::Error::Sys(Errno::EINTR)) => continue,
+                Err(e) => return Err(e.into())
+            }
+
+            // The kernel notifies also when the screen is redrawn, so check that the terminal actually changed
+            let active = read_active_tty(&mut self.file)?;
+            if active != self.active {
+                self.active = active;
+                return Ok(Some(active));
+            }
+        }
+    }
+
+}
+
+impl Iterator for VtWatcher {
+    type Item = Result<VtNumber>;
+
+    fn next(&mut self) -> Option<Result<VtNumber>> {
+        self.wait(None).transpose()
+    }
+}
+
+/// The descriptor becomes ready with `POLLPRI` when the active terminal might have changed:
+/// call [`VtWatcher::wait`] with a zero timeout to check.
+/// 
+/// [`VtWatcher::wait`]: crate::VtWatcher::wait
+impl AsRawFd for VtWatcher {
+    fn as_raw_fd(&self) -> RawFd {
+        self.file.as_raw_fd()
+    }
+}
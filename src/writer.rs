@@ -0,0 +1,173 @@
+use std::io::{self, Write};
+use std::sync::{Mutex, MutexGuard};
+use crate::vt::OwnedVt;
+
+/// A thread-safe writer sending its output to a virtual terminal, like a diagnostics console on `tty12`.
+/// 
+/// Writes are serialized by an internal lock, so a `VtWriter` can be shared among threads,
+/// and used as the target of logging libraries: with the `tracing-subscriber` feature
+/// it can be passed to `tracing_subscriber::fmt().with_writer(...)`, and with the `log` feature
+/// it can be installed directly as a `log` backend with `log::set_boxed_logger`.
+/// Any other library accepting a [`Write`] implementation can use it as well.
+/// 
+/// Long lines can optionally be wrapped at a given width with [`VtWriter::set_wrap_width`],
+/// ignoring escape sequences when counting columns.
+/// 
+/// [`Write`]: std::io::Write
+/// [`VtWriter::set_wrap_width`]: crate::VtWriter::set_wrap_width
+pub struct VtWriter {
+    state: Mutex<WriterState>,
+    wrap_width: Option<u16>
+}
+
+struct WriterState {
+    vt: OwnedVt,
+    column: u16,
+    in_escape: bool
+}
+
+impl VtWriter {
+
+    /// Creates a new writer sending its output to the given terminal.
+    pub fn new(vt: OwnedVt) -> VtWriter {
+        VtWriter {
+            state: Mutex::new(WriterState {
+                vt,
+                column: 0,
+                in_escape: false
+            }),
+            wrap_width: None
+        }
+    }
+
+    /// Sets the width at which lines are wrapped, or `None` to disable wrapping.
+    /// 
+    /// Returns `self` for chaining.
+    pub fn set_wrap_width(&mut self, width: Option<u16>) -> &mut Self {
+        self.wrap_width = width.filter(|w| *w > 0);
+        self
+    }
+
+    /// Locks the writer, returning a guard which writes to the terminal without interleaving with other threads
+    /// until it is dropped.
+    pub fn lock(&self) -> VtWriterGuard<'_> {
+        VtWriterGuard {
+            // A panic while writing does not leave the state inconsistent, so keep going
+            state: self.state.lock().unwrap_or_else(|e| e.into_inner()),
+            wrap_width: self.wrap_width
+        }
+    }
+
+    /// Consumes the writer, returning the underlying terminal.
+    pub fn into_inner(self) -> OwnedVt {
+        self.state.into_inner().unwrap_or_else(|e| e.into_inner()).vt
+    }
+
+}
+
+/// Guard returned by [`VtWriter::lock`], holding the lock of the writer.
+/// 
+/// [`VtWriter::lock`]: crate::VtWriter::lock
+pub struct VtWriterGuard<'w> {
+    state: MutexGuard<'w, WriterState>,
+    wrap_width: Option<u16>
+}
+
+impl<'w> Write for VtWriterGuard<'w> {
+
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let width = match self.wrap_width {
+            Some(width) => width,
+            None => return self.state.vt.write(buf)
+        };
+
+        // Write everything in a single call, inserting the line breaks where needed
+        let state = &mut *self.state;
+        let mut out = Vec::with_capacity(buf.len() + buf.len() / width as usize + 1);
+        for &b in buf {
+            if state.in_escape {
+                // Escape sequences end with a letter, except for the `[` introducing CSI sequences
+                state.in_escape = !(0x40..=0x7e).contains(&b) || b == b'[';
+            } else if b == 0x1b {
+                state.in_escape = true;
+            } else if b == b'\n' || b == b'\r' {
+                state.column = 0;
+            } else if b & 0xc0 != 0x80 && b >= 0x20 {
+                // Only the first byte of a UTF-8 character takes up a column
+                if state.column == width {
+                    out.push(b'\n');
+                    state.column = 0;
+                }
+                state.column += 1;
+            }
+            out.push(b);
+        }
+        state.vt.write_all(&out)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.state.vt.flush()
+    }
+
+}
+
+impl Write for VtWriter {
+
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.lock().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.lock().flush()
+    }
+
+}
+
+impl Write for &VtWriter {
+
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.lock().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.lock().flush()
+    }
+
+}
+
+/// Allows using a [`VtWriter`] as the writer of a `tracing_subscriber` formatter.
+/// Each event is written while holding the lock, so events from different threads do not interleave.
+/// 
+/// [`VtWriter`]: crate::VtWriter
+#[cfg(feature = "tracing-subscriber")]
+impl<'w> tracing_subscriber::fmt::MakeWriter<'w> for VtWriter {
+    type Writer = VtWriterGuard<'w>;
+
+    fn make_writer(&'w self) -> VtWriterGuard<'w> {
+        self.lock()
+    }
+}
+
+/// Allows installing a [`VtWriter`] as a `log` backend. Each record is written on its own line,
+/// prefixed by its level and target. Filtering is left to `log::set_max_level`.
+/// 
+/// [`VtWriter`]: crate::VtWriter
+#[cfg(feature = "log")]
+impl log::Log for VtWriter {
+
+    fn enabled(&self, _metadata: &log::Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &log::Record) {
+        // Errors are ignored, since there is nowhere else to report them
+        let line = format!("[{:<5} {}] {}\n", record.level(), record.target(), record.args());
+        let _ = self.lock().write_all(line.as_bytes());
+    }
+
+    fn flush(&self) {
+        let _ = self.lock().flush();
+    }
+
+}